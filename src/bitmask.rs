@@ -26,6 +26,7 @@ macro_rules! bitmask_impl {
                 Self(1 << bit)
             }
 
+            #[allow(clippy::should_implement_trait)]
             pub fn from_iter<I, S>(bits: I) -> Self where $t: Shl<S, Output=$t>, I: Iterator<Item = S> {
                 Self(bits.fold(0, |acc, x| acc | (1 << x)))
             }
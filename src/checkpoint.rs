@@ -0,0 +1,213 @@
+use std::fs;
+use std::path::Path;
+
+use crate::dfs_with_progress::SearchPosition;
+use crate::pipeline::{Shard, SourcePosition};
+
+/// A saved position of a pipeline run: which of the jobs that the search was
+/// split into have been finished, how far each job in progress had got, and
+/// the state of any filters which remember the puzzles they have seen.
+///
+/// Checkpoints are saved as text, for example:
+///
+/// ```text
+/// checkpoint
+/// jobs 64 70
+/// shard 1/2
+/// done 0,1,2,4
+/// job 3
+/// source 0 0 1 1 0.0375 2,1
+/// source 2 0.0371 0.0004 1 0.25 3
+/// state 3 123456789456789123789123456214365897365897214897214365531642978642978531978531642
+/// ```
+///
+/// Each running job lists the stack of searches it was in the middle of, from
+/// the base search upwards, as the pipeline step that the search feeds into,
+/// its progress offset and scale, and its `SearchPosition`.
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    pub split: usize,
+    pub shard: Option<Shard>,
+    pub jobs: Vec<JobStatus>,
+    pub filter_states: Vec<(usize, String)>,
+}
+
+#[derive(Clone, Debug)]
+pub enum JobStatus {
+    Pending,
+    Running(Vec<SourcePosition>),
+    Done,
+}
+
+impl Checkpoint {
+
+    /// Write the checkpoint to a file, replacing any previous checkpoint only once
+    /// the new one has been written in full
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some((step, _)) = self.filter_states.iter().find(|(_, entry)| entry.contains('\n')) {
+            return Err(format!("{}: the state of step {} has an entry spanning more than one line", path.display(), step));
+        }
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        fs::write(&temp_path, self.to_text())
+            .and_then(|_| fs::rename(&temp_path, path))
+            .map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    /// Read a checkpoint from a file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        Self::from_text(&text).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut lines = vec!["checkpoint".to_string(), format!("jobs {} {}", self.split, self.jobs.len())];
+        if let Some(shard) = self.shard { lines.push(format!("shard {}", shard)); }
+        let done = self.jobs.iter().enumerate().filter(|(_, status)| matches!(status, JobStatus::Done)).map(|(idx, _)| idx.to_string()).collect::<Vec<_>>();
+        if !done.is_empty() { lines.push(format!("done {}", done.join(","))); }
+        for (idx, status) in self.jobs.iter().enumerate() {
+            if let JobStatus::Running(sources) = status {
+                lines.push(format!("job {}", idx));
+                for source in sources {
+                    let search = &source.search;
+                    let levels = search.levels.iter().map(|taken| taken.to_string()).collect::<Vec<_>>();
+                    lines.push(format!("source {} {} {} {} {} {}", source.next_step, source.progress_offset, source.progress_scale,
+                        search.started as u8, search.progress, if levels.is_empty() { "-".to_string() } else { levels.join(",") }));
+                }
+            }
+        }
+        lines.extend(self.filter_states.iter().map(|(step, entry)| format!("state {} {}", step, entry)));
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate().map(|(idx, line)| (idx + 1, line, line.split_whitespace().collect::<Vec<_>>()));
+        if lines.next().map(|(_, _, words)| words) != Some(vec!["checkpoint"]) {
+            return Err("not a checkpoint file".to_string());
+        }
+
+        let mut checkpoint = Self { split: 0, shard: None, jobs: Vec::new(), filter_states: Vec::new() };
+        let mut current_job = None;
+        for (line, content, words) in lines {
+            let at_line = |message: String| format!("line {}, {}", line, message);
+            match words.as_slice() {
+                [] => {}
+                ["jobs", split, count] => {
+                    checkpoint.split = parse_number(split).map_err(at_line)?;
+                    checkpoint.jobs = vec![JobStatus::Pending; parse_number(count).map_err(at_line)?];
+                    current_job = None;
+                }
+                ["shard", shard] => checkpoint.shard = Some(Shard::from_str(shard).map_err(at_line)?),
+                ["done", jobs] => {
+                    for job in jobs.split(',') {
+                        *job_status(&mut checkpoint.jobs, job).map_err(at_line)? = JobStatus::Done;
+                    }
+                }
+                ["job", job] => {
+                    *job_status(&mut checkpoint.jobs, job).map_err(at_line)? = JobStatus::Running(Vec::new());
+                    current_job = Some(parse_number(job).map_err(at_line)?);
+                }
+                ["source", next_step, offset, scale, started, progress, levels] => {
+                    let Some(JobStatus::Running(sources)) = current_job.map(|job| &mut checkpoint.jobs[job]) else {
+                        return Err(at_line("`source` must follow a `job`".to_string()));
+                    };
+                    let levels = if *levels == "-" { Vec::new() } else { levels.split(',').map(parse_number).collect::<Result<_, _>>().map_err(at_line)? };
+                    sources.push(SourcePosition {
+                        next_step: parse_number(next_step).map_err(at_line)?,
+                        progress_offset: parse_fraction(offset).map_err(at_line)?,
+                        progress_scale: parse_fraction(scale).map_err(at_line)?,
+                        search: SearchPosition {
+                            started: *started == "1",
+                            progress: parse_fraction(progress).map_err(at_line)?,
+                            levels,
+                        },
+                    });
+                }
+                ["state", step, ..] => {
+                    // The entry is the rest of the line, which may itself contain whitespace
+                    let after_step = &content.trim_start()["state".len() ..].trim_start()[step.len() ..];
+                    let entry = after_step.strip_prefix(' ').unwrap_or(after_step);
+                    checkpoint.filter_states.push((parse_number(step).map_err(at_line)?, entry.to_string()));
+                }
+                _ => return Err(at_line(format!("unexpected `{}`", words.join(" ")))),
+            }
+        }
+        Ok(checkpoint)
+    }
+}
+
+fn job_status<'a>(jobs: &'a mut [JobStatus], job: &str) -> Result<&'a mut JobStatus, String> {
+    let count = jobs.len();
+    jobs.get_mut(parse_number(job)?).ok_or_else(|| format!("job {} is out of range, there are only {} jobs", job, count))
+}
+
+fn parse_number(s: &str) -> Result<usize, String> {
+    s.parse().map_err(|_| format!("expected a non-negative integer, found `{}`", s))
+}
+
+fn parse_fraction(s: &str) -> Result<f64, String> {
+    s.parse().map_err(|_| format!("expected a number, found `{}`", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::Filter;
+    use crate::pipeline::{PipelineFilter, RegionMaskedSudoku};
+    use crate::sudoku::Sudoku;
+
+    const GRID: &str = "417369825632158947958724316825437169791586432346912758289643571573291684164875293";
+
+    fn puzzle(empty: &[usize]) -> RegionMaskedSudoku {
+        let mut sudoku = Sudoku::from_str(GRID).unwrap();
+        empty.iter().for_each(|&cell| sudoku[cell] = 0);
+        RegionMaskedSudoku::from_sudoku(&sudoku)
+    }
+
+    #[test]
+    fn filter_state_survives_saving_and_loading() {
+        let filter = Filter::All(vec![Box::new(Filter::HasUniqueSolution), Box::new(Filter::non_equivalent())]);
+        assert!(filter.matches(&puzzle(&[0, 1])));
+        assert!(filter.matches(&puzzle(&[40, 50])));
+
+        // Entries other than those of `Filter` may contain whitespace
+        let mut filter_states = filter.save_state().into_iter().map(|entry| (2, entry)).collect::<Vec<_>>();
+        filter_states.push((3, "an entry  with spaces ".to_string()));
+        let checkpoint = Checkpoint {
+            split: 64,
+            shard: Some(Shard { index: 1, count: 2 }),
+            jobs: vec![
+                JobStatus::Done,
+                JobStatus::Running(vec![SourcePosition {
+                    next_step: 0,
+                    progress_offset: 0.0,
+                    progress_scale: 1.0,
+                    search: SearchPosition { started: true, progress: 0.25, levels: vec![2, 1] },
+                }]),
+                JobStatus::Pending,
+            ],
+            filter_states,
+        };
+        let text = checkpoint.to_text();
+        let loaded = Checkpoint::from_text(&text).unwrap();
+        assert_eq!(loaded.filter_states, checkpoint.filter_states);
+        assert_eq!(loaded.to_text(), text);
+
+        let restored = Filter::All(vec![Box::new(Filter::HasUniqueSolution), Box::new(Filter::non_equivalent())]);
+        for (_, entry) in loaded.filter_states.iter().filter(|(step, _)| *step == 2) {
+            restored.restore_state(entry).unwrap();
+        }
+        assert!(!restored.matches(&puzzle(&[0, 1])));
+        assert!(!restored.matches(&puzzle(&[40, 50])));
+        assert!(restored.matches(&puzzle(&[0, 80])));
+    }
+
+    #[test]
+    fn multiline_entries_are_not_saved() {
+        let checkpoint = Checkpoint { split: 1, shard: None, jobs: vec![JobStatus::Pending], filter_states: vec![(0, "two\nlines".to_string())] };
+        let path = std::env::temp_dir().join(format!("sudoku-utils-multiline-{}.checkpoint", std::process::id()));
+        assert!(checkpoint.save(&path).is_err());
+        assert!(!path.exists());
+    }
+}
@@ -9,32 +9,165 @@ pub trait DepthFirstTraversable {
     fn output(&mut self) -> Option<Self::Output>;
 }
 
+/// A subtree of a depth-first search, identified by the index of the step taken
+/// at each level on the way down from the root, together with the portion of
+/// the overall progress that it accounts for.
+///
+/// If `descend` is false then only the root of the subtree is visited - this is
+/// used for nodes whose children have been split off into subtrees of their own.
+#[derive(Clone)]
+pub struct Subtree {
+    pub path: Vec<usize>,
+    pub start: f64,
+    pub size: f64,
+    pub descend: bool,
+}
+
+impl Subtree {
+
+    /// The whole search tree
+    pub fn root() -> Self {
+        Self { path: Vec::new(), start: 0.0, size: 1.0, descend: true }
+    }
+
+    /// Split the search tree rooted at the given state into at least `min_count`
+    /// disjoint subtrees if possible, by expanding the top levels of the tree
+    /// one level at a time. The subtrees are returned in depth-first order.
+    pub fn split<T: DepthFirstTraversable>(root: &mut T, min_count: usize) -> Vec<Self> {
+        let mut frontier = vec![Self::root()];
+        while frontier.len() < min_count {
+            let mut next_frontier = Vec::new();
+            let mut expanded = false;
+            for subtree in frontier {
+                if !subtree.descend { next_frontier.push(subtree); continue; }
+
+                let applied_steps = apply_path(root, &subtree.path);
+                let child_count = if root.should_prune() { 0 } else { root.next_steps().len() };
+                if child_count == 0 {
+                    next_frontier.push(subtree);
+                } else {
+                    expanded = true;
+                    if root.output().is_some() {
+                        next_frontier.push(Self { path: subtree.path.clone(), start: subtree.start, size: 0.0, descend: false });
+                    }
+                    let (mut start, size) = (subtree.start, subtree.size / child_count as f64);
+                    for idx in 0 .. child_count {
+                        let mut path = subtree.path.clone(); path.push(idx);
+                        next_frontier.push(Self { path, start, size, descend: true });
+                        start += size;
+                    }
+                }
+                applied_steps.iter().rev().for_each(|step| root.revert_step(step));
+            }
+            frontier = next_frontier;
+            if !expanded { break; }
+        }
+        frontier
+    }
+}
+
+/// Walk down from the current state along the given path, returning the steps
+/// that were applied so that they can later be reverted.
+fn apply_path<T: DepthFirstTraversable>(state: &mut T, path: &[usize]) -> Vec<T::Step> {
+    path.iter().map(|&idx| {
+        let step = state.next_steps().nth(idx).unwrap();
+        state.apply_step(&step);
+        step
+    }).collect()
+}
+
+/// The position of a depth-first search, from which it can later be resumed.
+///
+/// Steps are identified by their index in the order given by `next_steps`, which
+/// must be the same each time for the same state. `levels` holds the number of
+/// steps taken so far at each level, the last of which has been applied.
+#[derive(Clone, Debug)]
+pub struct SearchPosition {
+    pub started: bool,
+    pub progress: f64,
+    pub levels: Vec<usize>,
+}
+
+/// A search whose position can be saved and later resumed
+pub trait ResumableSearch: Iterator {
+    type Output;
+
+    /// The current position of the search
+    fn save_position(&self) -> SearchPosition;
+
+    /// The output at the current position of the search, if there is one
+    fn current_output(&mut self) -> Option<Self::Output>;
+}
+
+struct Level<S> {
+    steps: Box<dyn ExactSizeIterator<Item = S>>,
+    step: Option<S>,
+    taken: usize,
+    increment: f64,
+}
+
 pub struct DepthFirstSearcherWithProgress<T, S> {
     state: T,
-    levels: Vec<(Box<dyn ExactSizeIterator<Item = S>>, Option<S>, f64)>,
+    levels: Vec<Level<S>>,
     progress: f64,
+    subtree: Subtree,
+    started: bool,
 }
 
 impl<T: DepthFirstTraversable> DepthFirstSearcherWithProgress<T, T::Step> {
 
     /// A new search with the given state as the root
     pub fn new(start_state: T) -> Self {
+        Self::for_subtree(start_state, Subtree::root())
+    }
+
+    /// A new search over only the given subtree of the search rooted at the given
+    /// state. Progress is reported as a fraction of the whole search.
+    pub fn for_subtree(mut start_state: T, subtree: Subtree) -> Self {
+        apply_path(&mut start_state, &subtree.path);
         Self {
             state: start_state,
             levels: Vec::new(),
             progress: 0.0,
+            subtree,
+            started: false,
+        }
+    }
+
+    /// Resume a search over the given subtree from a position that it previously reached
+    pub fn resume(start_state: T, subtree: Subtree, position: &SearchPosition) -> Result<Self, String> {
+        let mut searcher = Self::for_subtree(start_state, subtree);
+        searcher.started = position.started;
+        searcher.progress = position.progress;
+        for (depth, &taken) in position.levels.iter().enumerate() {
+            let mut steps = searcher.state.next_steps();
+            let len = steps.len();
+            if len == 0 || taken > len || (taken == 0 && depth + 1 < position.levels.len()) {
+                return Err("the saved position does not belong to this search".to_string());
+            }
+            let step = if taken > 0 { steps.nth(taken - 1) } else { None };
+            if let Some(step) = &step { searcher.state.apply_step(step); }
+            let increment = searcher.progress_increment() / len as f64;
+            searcher.levels.push(Level { steps, step, taken, increment });
         }
+        Ok(searcher)
     }
 
     /// Take a single step onwards in the depth-first search
+    #[allow(clippy::len_zero)]
     fn step(&mut self) -> bool {
 
+        // The root is visited on the first step, and if it didn't lead any
+        // deeper then there's nothing more to do.
+        if self.started && self.levels.is_empty() { return false; }
+        self.started = true;
+
         // Pop all of the levels whose steps have been fully explored.
         // If everything is popped then we've finished the search.
-        while let Some((steps, _, _)) = self.levels.last() {
-            if steps.len() == 0 {
-                let (_, step, _) = self.levels.pop().unwrap();
-                if let Some(step) = step { self.state.revert_step(&step); }
+        while let Some(level) = self.levels.last() {
+            if level.steps.len() == 0 {
+                let level = self.levels.pop().unwrap();
+                if let Some(step) = level.step { self.state.revert_step(&step); }
                 if self.levels.len() == 0 { return false; }
             } else {
                 break;
@@ -42,15 +175,16 @@ impl<T: DepthFirstTraversable> DepthFirstSearcherWithProgress<T, T::Step> {
         }
 
         // Advance the deepest level by one step
-        if let Some((steps, prev_step, _)) = self.levels.last_mut() {
-            if let Some(prev_step) = prev_step { self.state.revert_step(prev_step); }
-            let next_step = steps.next().unwrap();
+        if let Some(level) = self.levels.last_mut() {
+            if let Some(prev_step) = &level.step { self.state.revert_step(prev_step); }
+            let next_step = level.steps.next().unwrap();
             self.state.apply_step(&next_step);
-            *prev_step = Some(next_step);
+            level.step = Some(next_step);
+            level.taken += 1;
         }
 
         // Check if we should prune at this state and don't go deeper if so
-        if self.state.should_prune() { 
+        if !self.subtree.descend || self.state.should_prune() {
             self.progress += self.progress_increment();
             return true;
         }
@@ -58,8 +192,8 @@ impl<T: DepthFirstTraversable> DepthFirstSearcherWithProgress<T, T::Step> {
         // Deepen the search by one level if possible
         let next_steps = self.state.next_steps();
         if next_steps.len() > 0 {
-            let next_increment = self.progress_increment() / next_steps.len() as f64;
-            self.levels.push((next_steps, None, next_increment));
+            let increment = self.progress_increment() / next_steps.len() as f64;
+            self.levels.push(Level { steps: next_steps, step: None, taken: 0, increment });
         } else {
             self.progress += self.progress_increment();
         }
@@ -69,7 +203,7 @@ impl<T: DepthFirstTraversable> DepthFirstSearcherWithProgress<T, T::Step> {
 
     /// Get the progress increment associated with a step at the current level
     fn progress_increment(&self) -> f64 {
-        self.levels.last().map(|(_, _, inc)| *inc).unwrap_or(1.0)
+        self.levels.last().map(|level| level.increment).unwrap_or(1.0)
     }
 }
 
@@ -79,9 +213,26 @@ impl<T: DepthFirstTraversable> Iterator for DepthFirstSearcherWithProgress<T, T:
     fn next(&mut self) -> Option<Self::Item> {
         while self.step() {
             if let Some(output) = self.state.output() {
-                return Some((self.progress, self.progress_increment(), output));
+                let progress = self.subtree.start + self.progress * self.subtree.size;
+                return Some((progress, self.progress_increment() * self.subtree.size, output));
             }
         }
         None
     }
 }
+
+impl<T: DepthFirstTraversable> ResumableSearch for DepthFirstSearcherWithProgress<T, T::Step> {
+    type Output = T::Output;
+
+    fn save_position(&self) -> SearchPosition {
+        SearchPosition {
+            started: self.started,
+            progress: self.progress,
+            levels: self.levels.iter().map(|level| level.taken).collect(),
+        }
+    }
+
+    fn current_output(&mut self) -> Option<Self::Output> {
+        self.state.output()
+    }
+}
@@ -5,34 +5,77 @@ use std::rc::Rc;
 use itertools::Itertools;
 
 use crate::bitmask::Bitmask;
-use crate::dfs_with_progress::{DepthFirstSearcherWithProgress, DepthFirstTraversable};
-use crate::pipeline::RegionMaskedSudoku;
+use crate::dfs_with_progress::{DepthFirstSearcherWithProgress, DepthFirstTraversable, SearchPosition, Subtree};
+use crate::fast_solver::FastBruteForceSolver;
+use crate::pipeline::{PipelineExpansion, PipelineSearch, RegionMaskedSudoku};
+use crate::sudoku::parse_cell;
 use crate::symmetry::DihedralSubgroup;
 
 pub enum Expansion {
     PlusN { n: usize, symmetry: DihedralSubgroup, excluded_cells: Vec<(usize, usize)> },
+    /// Every minimal puzzle with a unique solution which can be reached by removing
+    /// clues from a puzzle with a unique solution. Clues are removed a whole orbit of
+    /// the symmetry at a time, and protected clues, and their orbits, are never removed.
+    MinimalSubpuzzles { symmetry: DihedralSubgroup, protected_cells: Vec<(usize, usize)> },
+    /// Every puzzle made by removing the clues of `n` orbits of the symmetry, never
+    /// removing protected clues or their orbits
+    MinusN { n: usize, symmetry: DihedralSubgroup, protected_cells: Vec<(usize, usize)> },
 }
 
-impl Expansion {
-    pub fn expand(&self, sudoku: Rc<RefCell<RegionMaskedSudoku>>) -> Box<dyn Iterator<Item = (f64, f64, Rc<RefCell<RegionMaskedSudoku>>)>> {
+impl PipelineExpansion for Expansion {
+    fn expand(&self, sudoku: Rc<RefCell<RegionMaskedSudoku>>) -> PipelineSearch {
         match self {
             Self::PlusN { n, symmetry, excluded_cells } => {
                 let root = PlusNSearchState::for_sudoku_and_symmetry(*n, sudoku, *symmetry, excluded_cells);
                 Box::new(DepthFirstSearcherWithProgress::new(root))
             }
+            Self::MinimalSubpuzzles { symmetry, protected_cells } => {
+                let root = MinimalSubpuzzlesSearchState::for_sudoku_and_symmetry(sudoku, *symmetry, protected_cells);
+                Box::new(DepthFirstSearcherWithProgress::new(root))
+            }
+            Self::MinusN { n, symmetry, protected_cells } => {
+                let root = MinusNSearchState::for_sudoku_and_symmetry(*n, sudoku, *symmetry, protected_cells);
+                Box::new(DepthFirstSearcherWithProgress::new(root))
+            }
         }
     }
 
-    pub fn plus_n(n: usize, symmetry: DihedralSubgroup, excluded_cells_str: &str) -> Self {
-        let excluded_cells = excluded_cells_str.split(",").map(|s| s.trim()).map(|s| {
-            let (_, rc) = s.split_once("r").unwrap();
-            let (r, c) = rc.split("c").map(|d| d.parse::<usize>().unwrap()).collect_tuple().unwrap();
-            (r - 1, c - 1)
-        }).collect();
-        Self::PlusN { n, symmetry, excluded_cells }
+    fn resume(&self, sudoku: Rc<RefCell<RegionMaskedSudoku>>, position: &SearchPosition) -> Result<PipelineSearch, String> {
+        match self {
+            Self::PlusN { n, symmetry, excluded_cells } => {
+                let root = PlusNSearchState::for_sudoku_and_symmetry(*n, sudoku, *symmetry, excluded_cells);
+                Ok(Box::new(DepthFirstSearcherWithProgress::resume(root, Subtree::root(), position)?))
+            }
+            Self::MinimalSubpuzzles { symmetry, protected_cells } => {
+                let root = MinimalSubpuzzlesSearchState::for_sudoku_and_symmetry(sudoku, *symmetry, protected_cells);
+                Ok(Box::new(DepthFirstSearcherWithProgress::resume(root, Subtree::root(), position)?))
+            }
+            Self::MinusN { n, symmetry, protected_cells } => {
+                let root = MinusNSearchState::for_sudoku_and_symmetry(*n, sudoku, *symmetry, protected_cells);
+                Ok(Box::new(DepthFirstSearcherWithProgress::resume(root, Subtree::root(), position)?))
+            }
+        }
     }
 }
 
+impl Expansion {
+    pub fn plus_n(n: usize, symmetry: DihedralSubgroup, excluded_cells_str: &str) -> Result<Self, String> {
+        Ok(Self::PlusN { n, symmetry, excluded_cells: parse_cells(excluded_cells_str)? })
+    }
+
+    pub fn minus_n(n: usize, symmetry: DihedralSubgroup, protected_cells_str: &str) -> Result<Self, String> {
+        Ok(Self::MinusN { n, symmetry, protected_cells: parse_cells(protected_cells_str)? })
+    }
+
+    pub fn minimal_subpuzzles(symmetry: DihedralSubgroup, protected_cells_str: &str) -> Result<Self, String> {
+        Ok(Self::MinimalSubpuzzles { symmetry, protected_cells: parse_cells(protected_cells_str)? })
+    }
+}
+
+fn parse_cells(cells_str: &str) -> Result<Vec<(usize, usize)>, String> {
+    cells_str.split(",").map(|s| s.trim()).filter(|s| !s.is_empty()).map(parse_cell).collect()
+}
+
 struct PlusNSearchState {
     sudoku: Rc<RefCell<RegionMaskedSudoku>>,
     orbits: [Bitmask<u128>; 81],
@@ -69,6 +112,7 @@ impl DepthFirstTraversable for PlusNSearchState {
     type Step = PlusNSearchStep;
     type Output = Rc<RefCell<RegionMaskedSudoku>>;
 
+    #[allow(clippy::redundant_closure)]
     fn next_steps(&mut self) -> Box<dyn ExactSizeIterator<Item = Self::Step>> {
         if let Some(idx) = self.pending_placement {
             Box::new(self.sudoku.borrow().candidates(idx).as_bit_iter().map(move |d| PlusNSearchStep::PlaceDigit(idx, d as u8)))
@@ -82,6 +126,7 @@ impl DepthFirstTraversable for PlusNSearchState {
         }
     }
 
+    #[allow(clippy::match_ref_pats)]
     fn apply_step(&mut self, step: &Self::Step) {
         match step {
             &PlusNSearchStep::AddCell(cell) => {
@@ -100,6 +145,7 @@ impl DepthFirstTraversable for PlusNSearchState {
         }
     }
     
+    #[allow(clippy::match_ref_pats)]
     fn revert_step(&mut self, step: &Self::Step) {
         match step {
             &PlusNSearchStep::PlaceDigit(cell, d) => {
@@ -124,3 +170,191 @@ impl DepthFirstTraversable for PlusNSearchState {
         (self.required_cells.is_empty() && self.pending_placement.is_none()).then(|| self.sudoku.clone())
     }
 }
+
+/// The clues of each orbit of the symmetry which has any clues, and no protected cells
+fn removable_orbits(sudoku: &RegionMaskedSudoku, symmetry: DihedralSubgroup, protected_cells: &[(usize, usize)]) -> Vec<Vec<(usize, u8)>> {
+    let protected = Bitmask::<u128>::from_iter(protected_cells.iter().map(|&(y, x)| 9 * y + x));
+    (0 .. 81)
+        .map(|cell| symmetry.orbits()[cell])
+        .filter(|orbit| orbit.iter().all(|&cell| !protected.contains(cell)))
+        .map(|orbit| orbit.iter().map(|&cell| (cell, sudoku.sudoku()[cell])).filter(|&(_, digit)| digit != 0).collect::<Vec<_>>())
+        .filter(|clues| !clues.is_empty())
+        .unique()
+        .collect()
+}
+
+struct MinusNSearchState {
+    sudoku: Rc<RefCell<RegionMaskedSudoku>>,
+    /// The clues of each orbit which may be removed, in the order they are tried
+    orbits: Vec<Vec<(usize, u8)>>,
+    /// The orbits removed so far, in increasing order
+    removed: Vec<usize>,
+    n: usize,
+}
+
+impl MinusNSearchState {
+    pub fn for_sudoku_and_symmetry(n: usize, sudoku: Rc<RefCell<RegionMaskedSudoku>>, symmetry: DihedralSubgroup, protected_cells: &[(usize, usize)]) -> Self {
+        let orbits = removable_orbits(&sudoku.borrow(), symmetry, protected_cells);
+        Self { sudoku, orbits, removed: Vec::new(), n }
+    }
+
+    fn next_orbit(&self) -> usize {
+        self.removed.last().map_or(0, |&orbit| orbit + 1)
+    }
+}
+
+impl DepthFirstTraversable for MinusNSearchState {
+    type Step = usize;
+    type Output = Rc<RefCell<RegionMaskedSudoku>>;
+
+    fn next_steps(&mut self) -> Box<dyn ExactSizeIterator<Item = Self::Step>> {
+        if self.removed.len() == self.n { return Box::new(empty()); }
+        Box::new(self.next_orbit() .. self.orbits.len())
+    }
+
+    fn apply_step(&mut self, &orbit: &Self::Step) {
+        let mut sudoku = self.sudoku.borrow_mut();
+        self.orbits[orbit].iter().for_each(|&(cell, digit)| sudoku.unplace(cell, digit));
+        self.removed.push(orbit);
+    }
+
+    fn revert_step(&mut self, &orbit: &Self::Step) {
+        let mut sudoku = self.sudoku.borrow_mut();
+        self.orbits[orbit].iter().for_each(|&(cell, digit)| sudoku.place(cell, digit));
+        self.removed.pop();
+    }
+
+    fn should_prune(&mut self) -> bool {
+        self.orbits.len() - self.next_orbit() < self.n - self.removed.len()
+    }
+
+    fn output(&mut self) -> Option<Self::Output> {
+        (self.removed.len() == self.n).then(|| self.sudoku.clone())
+    }
+}
+
+struct MinimalSubpuzzlesSearchState {
+    sudoku: Rc<RefCell<RegionMaskedSudoku>>,
+    /// The clues of each orbit which may be removed, in the order they are tried
+    orbits: Vec<Vec<(usize, u8)>>,
+    /// The orbits removed so far, in increasing order
+    removed: Vec<usize>,
+    root_is_unique: bool,
+    is_unique: bool,
+}
+
+impl MinimalSubpuzzlesSearchState {
+    pub fn for_sudoku_and_symmetry(sudoku: Rc<RefCell<RegionMaskedSudoku>>, symmetry: DihedralSubgroup, protected_cells: &[(usize, usize)]) -> Self {
+        let orbits = removable_orbits(&sudoku.borrow(), symmetry, protected_cells);
+        let root_is_unique = FastBruteForceSolver::has_unique_solution(sudoku.borrow().sudoku());
+        Self { sudoku, orbits, removed: Vec::new(), root_is_unique, is_unique: root_is_unique }
+    }
+
+    /// Check whether the puzzle would still have a unique solution without the given orbit
+    fn is_removable(&self, orbit: usize) -> bool {
+        let mut sudoku = self.sudoku.borrow().sudoku().clone();
+        self.orbits[orbit].iter().for_each(|&(cell, _)| sudoku[cell] = 0);
+        FastBruteForceSolver::has_unique_solution(&sudoku)
+    }
+}
+
+impl DepthFirstTraversable for MinimalSubpuzzlesSearchState {
+    type Step = usize;
+    type Output = Rc<RefCell<RegionMaskedSudoku>>;
+
+    // Removing orbits only in increasing order reaches each set of removals once. Any
+    // set that keeps the solution unique can be reached, as all of its subsets do too.
+    fn next_steps(&mut self) -> Box<dyn ExactSizeIterator<Item = Self::Step>> {
+        let start = self.removed.last().map_or(0, |&orbit| orbit + 1);
+        Box::new(start .. self.orbits.len())
+    }
+
+    fn apply_step(&mut self, &orbit: &Self::Step) {
+        let mut sudoku = self.sudoku.borrow_mut();
+        self.orbits[orbit].iter().for_each(|&(cell, digit)| sudoku.unplace(cell, digit));
+        self.is_unique = FastBruteForceSolver::has_unique_solution(sudoku.sudoku());
+        self.removed.push(orbit);
+    }
+
+    fn revert_step(&mut self, &orbit: &Self::Step) {
+        let mut sudoku = self.sudoku.borrow_mut();
+        self.orbits[orbit].iter().for_each(|&(cell, digit)| sudoku.place(cell, digit));
+        self.removed.pop();
+        // The search never goes on from a puzzle without a unique solution
+        self.is_unique = !self.removed.is_empty() || self.root_is_unique;
+    }
+
+    fn should_prune(&mut self) -> bool {
+        !self.is_unique
+    }
+
+    fn output(&mut self) -> Option<Self::Output> {
+        let is_minimal = self.is_unique && (0 .. self.orbits.len()).all(|orbit| self.removed.contains(&orbit) || !self.is_removable(orbit));
+        is_minimal.then(|| self.sudoku.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::sudoku::Sudoku;
+
+    /// A minimal puzzle with a unique solution
+    const PUZZLE: &str = "000290000087605000100007000000000407243000100000000009038000010020400900000089503";
+
+    fn expand(expansion: &Expansion, puzzle: &Sudoku) -> Vec<Sudoku> {
+        let sudoku = Rc::new(RefCell::new(RegionMaskedSudoku::from_sudoku(puzzle)));
+        expansion.expand(sudoku).map(|(_, _, sudoku)| sudoku.borrow().sudoku().clone()).collect()
+    }
+
+    fn clues(sudoku: &Sudoku) -> Vec<usize> {
+        (0 .. 81).filter(|&cell| sudoku[cell] != 0).collect()
+    }
+
+    fn is_subpuzzle_of(subpuzzle: &Sudoku, puzzle: &Sudoku) -> bool {
+        puzzle.digits().zip(subpuzzle.digits()).all(|(&clue, &digit)| digit == 0 || digit == clue)
+    }
+
+    #[test]
+    fn minimal_subpuzzles_match_brute_force() {
+        // Adding r1c7 from the solution makes both it and r5c7 redundant
+        let mut puzzle = Sudoku::from_str(PUZZLE).unwrap();
+        puzzle[6] = 7;
+        assert_eq!(FastBruteForceSolver::redundant_clues(&puzzle), vec![6, 42]);
+
+        // Protect the clues outside of rows 1, 2 and 5, so that every subset of the rest can be tried
+        let (removable, protected): (Vec<_>, Vec<_>) = clues(&puzzle).into_iter().partition(|&cell| [0, 1, 4].contains(&(cell / 9)));
+        let protected_cells = protected.iter().map(|&cell| (cell / 9, cell % 9)).collect();
+        let expansion = Expansion::MinimalSubpuzzles { symmetry: DihedralSubgroup::Trivial, protected_cells };
+        let outputs = expand(&expansion, &puzzle);
+
+        let expected = (0 .. 1 << removable.len()).filter_map(|removed: usize| {
+            let mut subpuzzle = puzzle.clone();
+            removable.iter().enumerate().filter(|&(idx, _)| removed & 1 << idx != 0).for_each(|(_, &cell)| subpuzzle[cell] = 0);
+            let is_minimal = FastBruteForceSolver::has_unique_solution(&subpuzzle)
+                && FastBruteForceSolver::redundant_clues(&subpuzzle).iter().all(|cell| protected.contains(cell));
+            is_minimal.then_some(subpuzzle)
+        }).collect::<HashSet<_>>();
+        let mut swapped = puzzle.clone();
+        swapped[42] = 0;
+        assert!(expected.contains(&Sudoku::from_str(PUZZLE).unwrap()) && expected.contains(&swapped));
+        assert_eq!(outputs.len(), expected.len());
+        assert_eq!(outputs.into_iter().collect::<HashSet<_>>(), expected);
+    }
+
+    #[test]
+    fn minus_n_removes_each_combination_of_clues() {
+        let puzzle = Sudoku::from_str(PUZZLE).unwrap();
+        let clue_count = clues(&puzzle).len();
+        for n in 1 ..= 3 {
+            let expansion = Expansion::MinusN { n, symmetry: DihedralSubgroup::Trivial, protected_cells: Vec::new() };
+            let outputs = expand(&expansion, &puzzle);
+            let combinations = (0 .. n).fold(1, |combinations, idx| combinations * (clue_count - idx) / (idx + 1));
+            assert_eq!(outputs.len(), combinations);
+            assert!(outputs.iter().all(|output| is_subpuzzle_of(output, &puzzle) && clues(output).len() == clue_count - n));
+            assert_eq!(outputs.iter().collect::<HashSet<_>>().len(), outputs.len());
+        }
+    }
+}
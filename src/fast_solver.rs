@@ -30,6 +30,51 @@ impl<'a> Solutions<'a> {
     }
 }
 
+/// Whether a candidate appears in some, all or none of the solutions of a puzzle
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CandidatePresence {
+    NoSolutions,
+    SomeSolutions,
+    AllSolutions,
+}
+
+/// For each cell and candidate of a puzzle, whether that candidate appears in
+/// some, all or none of the solutions, and optionally how many solutions it
+/// appears in, up to a limit. Both are indexed by cell and then by digit - 1.
+pub struct Backbone {
+    pub presence: [[CandidatePresence; 9]; N_CELLS],
+    pub counts: Option<[[usize; 9]; N_CELLS]>,
+}
+
+impl Backbone {
+    pub fn presence(&self, cell: usize, digit: u8) -> CandidatePresence {
+        self.presence[cell][digit as usize - 1]
+    }
+
+    pub fn count(&self, cell: usize, digit: u8) -> Option<usize> {
+        self.counts.as_ref().map(|counts| counts[cell][digit as usize - 1])
+    }
+}
+
+/// A lazy iterator over the solutions of a puzzle, which keeps a stack of the
+/// branches still to be explored rather than recursing like the rest of the solver.
+pub struct SolutionIter {
+    stack: Vec<FastBruteForceSolver>,
+}
+
+impl Iterator for SolutionIter {
+    type Item = Sudoku;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(mut solver) = self.stack.pop() {
+            if solver.solve(usize::MAX, &mut Solutions::Count(0)).is_err() { continue; }
+            if solver.is_solved() { return Some(solver.extract_solution()); }
+            self.stack.extend(solver.branches().into_iter().rev());
+        }
+        None
+    }
+}
+
 /// A helper type for unchecked indexing into arrays, which speeds up 
 /// the solver by up to 10% on the hardest puzzles.
 #[derive(Clone)]
@@ -71,10 +116,112 @@ impl FastBruteForceSolver {
         Self::from_sudoku(sudoku).map(|s| s.count_solutions_up_to(2) == 1).unwrap_or(false)
     }
 
+    pub fn unique_solution(sudoku: &Sudoku) -> Option<Sudoku> {
+        let mut solutions = Self::from_sudoku(sudoku).ok()?.all_solutions_up_to(2);
+        if solutions.len() == 1 { solutions.pop() } else { None }
+    }
+
+    /// Up to the first `n` solutions, in the order that the solver finds them
+    pub fn first_solutions(sudoku: &Sudoku, n: usize) -> Vec<Sudoku> {
+        Self::from_sudoku(sudoku).map(|s| s.all_solutions_up_to(n)).unwrap_or_default()
+    }
+
+    /// A lazy iterator over all of the solutions, in the same order as `first_solutions`
+    pub fn solutions(sudoku: &Sudoku) -> SolutionIter {
+        let stack = Self::from_sudoku(sudoku).ok()
+            .and_then(|mut solver| solver.find_naked_singles().ok().map(|_| solver))
+            .into_iter()
+            .collect();
+        SolutionIter { stack }
+    }
+
+    /// Work out which candidates appear in some, all or none of the solutions, and
+    /// optionally how many solutions each candidate appears in, counting up to
+    /// the given limit.
+    pub fn backbone(sudoku: &Sudoku, count_limit: Option<usize>) -> Backbone {
+        let mut presence = [[CandidatePresence::NoSolutions; 9]; N_CELLS];
+
+        // Each solution found shows that all of its candidates appear in some
+        // solution, so only the remaining candidates need checking separately
+        let mark_solution = |presence: &mut [[CandidatePresence; 9]; N_CELLS], solution: &Sudoku| {
+            for (cell, &digit) in solution.digits().enumerate() {
+                presence[cell][digit as usize - 1] = CandidatePresence::SomeSolutions;
+            }
+        };
+        let mut with_placement = sudoku.clone();
+        for cell in 0 .. N_CELLS {
+            for digit in 1 ..= N_DIGITS as u8 {
+                if presence[cell][digit as usize - 1] != CandidatePresence::NoSolutions { continue; }
+                if sudoku[cell] != 0 && sudoku[cell] != digit { continue; }
+                with_placement[cell] = digit;
+                if let Some(solution) = Self::first_solutions(&with_placement, 1).pop() {
+                    mark_solution(&mut presence, &solution);
+                }
+            }
+            with_placement[cell] = sudoku[cell];
+        }
+
+        // A candidate is in every solution if it's the only one left in its cell
+        for candidates in presence.iter_mut() {
+            let mut some = candidates.iter_mut().filter(|presence| **presence == CandidatePresence::SomeSolutions);
+            if let (Some(only), None) = (some.next(), some.next()) {
+                *only = CandidatePresence::AllSolutions;
+            }
+        }
+
+        let counts = count_limit.map(|limit| {
+            let total = Self::count_solutions_with_limit(sudoku, limit);
+            let mut counts = [[0; 9]; N_CELLS];
+            for cell in 0 .. N_CELLS {
+                for digit in 1 ..= N_DIGITS as u8 {
+                    counts[cell][digit as usize - 1] = match presence[cell][digit as usize - 1] {
+                        CandidatePresence::NoSolutions => 0,
+                        CandidatePresence::AllSolutions => total,
+                        CandidatePresence::SomeSolutions => {
+                            with_placement[cell] = digit;
+                            Self::count_solutions_with_limit(&with_placement, limit)
+                        }
+                    };
+                }
+                with_placement[cell] = sudoku[cell];
+            }
+            counts
+        });
+
+        Backbone { presence, counts }
+    }
+
     pub fn count_solutions(sudoku: &Sudoku) -> usize {
         Self::from_sudoku(sudoku).map(|s| s.count_solutions_up_to(usize::MAX)).unwrap_or(0)
     }
 
+    pub fn count_solutions_with_limit(sudoku: &Sudoku, limit: usize) -> usize {
+        Self::from_sudoku(sudoku).map(|s| s.count_solutions_up_to(limit)).unwrap_or(0)
+    }
+
+    /// Check that removing any one clue from a puzzle with a unique solution
+    /// leaves it with more than one, stopping at the first clue that doesn't
+    pub fn is_minimal(sudoku: &Sudoku) -> bool {
+        let mut without_clue = sudoku.clone();
+        !(0 .. N_CELLS).any(|cell| Self::is_redundant_clue(&mut without_clue, cell))
+    }
+
+    /// The clues of a puzzle with a unique solution which could each be removed
+    /// on their own while keeping the solution unique
+    pub fn redundant_clues(sudoku: &Sudoku) -> Vec<usize> {
+        let mut without_clue = sudoku.clone();
+        (0 .. N_CELLS).filter(|&cell| Self::is_redundant_clue(&mut without_clue, cell)).collect()
+    }
+
+    fn is_redundant_clue(sudoku: &mut Sudoku, cell: usize) -> bool {
+        let digit = sudoku[cell];
+        if digit == 0 { return false; }
+        sudoku[cell] = 0;
+        let redundant = Self::has_unique_solution(sudoku);
+        sudoku[cell] = digit;
+        redundant
+    }
+
     fn from_sudoku(sudoku: &Sudoku) -> Result<Self, Unsolvable> {
         let mut solver = Self {
             possible_cells: UncheckedIndexArray([ALL; N_SUBBANDS]),
@@ -104,6 +251,7 @@ impl FastBruteForceSolver {
         solutions.len()
     }
 
+    #[allow(clippy::collapsible_if)]
     fn solutions_up_to(mut self, limit: usize, solutions: &mut Solutions) {
         if self.find_naked_singles().is_ok() {
             if self.solve(limit, solutions).is_ok() {
@@ -220,6 +368,29 @@ impl FastBruteForceSolver {
         }
     }
 
+    /// The states resulting from each value of the cell that guess() would branch on,
+    /// in the order that it would try them
+    fn branches(&self) -> Vec<Self> {
+        let bivalue_cell = (0 .. N_BANDS).find_map(|band| Some((band, MaskIter::<u32>::from(self.bivalue_cells[band]).peek()?)));
+        let some_cell = || (0 .. N_BANDS).flat_map(|band| {
+            let one_unsolved_cell = MaskIter::<u32>::from(self.unsolved_cells[band]).peek()?;
+            let n_candidates = (band..).step_by(3).take(N_DIGITS)
+                .filter(|&subband| self.possible_cells[subband] & one_unsolved_cell != NONE)
+                .count();
+            Some((n_candidates, band, one_unsolved_cell))
+        }).min().map(|(_, band, cell)| (band, cell));
+
+        let Some((band, cell_mask)) = bivalue_cell.or_else(some_cell) else { return Vec::new(); };
+        (band..).step_by(3).take(N_DIGITS)
+            .filter(|&subband| self.possible_cells[subband] & cell_mask != NONE)
+            .map(|subband| {
+                let mut branch = self.clone();
+                branch.insert_value_by_mask(subband, cell_mask);
+                branch
+            })
+            .collect()
+    }
+
     /// Store the current solution
     fn store_solution(&self, solutions: &mut Solutions) {
         match solutions {
@@ -732,3 +903,114 @@ fn shrink_mask(cell_mask: u32) -> u32 {
     ]);
     MASKS[cell_mask as usize]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A minimal puzzle with a unique solution
+    const PUZZLE: &str = "000290000087605000100007000000000407243000100000000009038000010020400900000089503";
+
+    fn sudoku(puzzle: &str) -> Sudoku {
+        Sudoku::from_str(puzzle).unwrap()
+    }
+
+    /// The same puzzle without one of its clues, which gives it five solutions
+    fn several_solutions() -> Sudoku {
+        let mut sudoku = sudoku(PUZZLE);
+        sudoku[42] = 0;
+        sudoku
+    }
+
+    fn is_solution_of(solution: &Sudoku, puzzle: &Sudoku) -> bool {
+        FastBruteForceSolver::has_unique_solution(solution) && solution.digits().all(|&digit| digit != 0)
+            && puzzle.digits().zip(solution.digits()).all(|(&clue, &digit)| clue == 0 || clue == digit)
+    }
+
+    #[test]
+    fn unique_solutions() {
+        let puzzle = sudoku(PUZZLE);
+        let solution = FastBruteForceSolver::unique_solution(&puzzle).unwrap();
+        assert!(is_solution_of(&solution, &puzzle));
+        assert_eq!(FastBruteForceSolver::first_solutions(&puzzle, 5), vec![solution.clone()]);
+        assert_eq!(FastBruteForceSolver::solutions(&puzzle).collect::<Vec<_>>(), vec![solution]);
+    }
+
+    #[test]
+    fn several_solutions_are_enumerated_in_order() {
+        let puzzle = several_solutions();
+        assert_eq!(FastBruteForceSolver::unique_solution(&puzzle), None);
+
+        let all = FastBruteForceSolver::solutions(&puzzle).collect::<Vec<_>>();
+        assert_eq!(all.len(), 5);
+        assert!(all.iter().all(|solution| is_solution_of(solution, &puzzle)));
+        assert!(all.iter().enumerate().all(|(idx, solution)| !all[.. idx].contains(solution)));
+        for n in 0 ..= 6 {
+            let first = FastBruteForceSolver::first_solutions(&puzzle, n);
+            assert_eq!(first.len(), n.min(5));
+            assert_eq!(first, FastBruteForceSolver::solutions(&puzzle).take(n).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn no_solutions() {
+        // The first two clues are both 4 in the same row
+        let puzzle = sudoku(&format!("44{}", &PUZZLE[2 ..]));
+        assert_eq!(FastBruteForceSolver::unique_solution(&puzzle), None);
+        assert!(FastBruteForceSolver::first_solutions(&puzzle, 3).is_empty());
+        assert_eq!(FastBruteForceSolver::solutions(&puzzle).next(), None);
+
+        // Clues which don't clash directly, but leave no digit for a cell
+        let puzzle = sudoku(&format!("{}{}", "12345678.........9", &".".repeat(63)));
+        assert!(FastBruteForceSolver::first_solutions(&puzzle, 3).is_empty());
+        assert_eq!(FastBruteForceSolver::solutions(&puzzle).next(), None);
+    }
+
+    #[test]
+    fn minimal_puzzles_have_no_redundant_clues() {
+        let puzzle = sudoku(PUZZLE);
+        assert!(FastBruteForceSolver::is_minimal(&puzzle));
+        assert!(FastBruteForceSolver::redundant_clues(&puzzle).is_empty());
+
+        // Adding r3c3 from the solution doesn't make any of the other clues redundant
+        let mut puzzle = puzzle;
+        puzzle[20] = 2;
+        assert!(!FastBruteForceSolver::is_minimal(&puzzle));
+        assert_eq!(FastBruteForceSolver::redundant_clues(&puzzle), vec![20]);
+    }
+
+    #[test]
+    fn backbone_agrees_with_the_solutions() {
+        let puzzle = several_solutions();
+        let solutions = FastBruteForceSolver::solutions(&puzzle).collect::<Vec<_>>();
+        let backbone = FastBruteForceSolver::backbone(&puzzle, None);
+        assert!(backbone.counts.is_none());
+        for cell in 0 .. N_CELLS {
+            for digit in 1 ..= 9 {
+                let count = solutions.iter().filter(|solution| solution[cell] == digit).count();
+                let expected = match count {
+                    0 => CandidatePresence::NoSolutions,
+                    _ if count == solutions.len() => CandidatePresence::AllSolutions,
+                    _ => CandidatePresence::SomeSolutions,
+                };
+                assert_eq!(backbone.presence(cell, digit), expected, "r{}c{} digit {}", cell / 9 + 1, cell % 9 + 1, digit);
+            }
+        }
+        assert!((0 .. N_CELLS).any(|cell| (1 ..= 9).any(|digit| backbone.presence(cell, digit) == CandidatePresence::SomeSolutions)));
+    }
+
+    #[test]
+    fn backbone_counts_stop_at_the_limit() {
+        let puzzle = several_solutions();
+        let solutions = FastBruteForceSolver::solutions(&puzzle).collect::<Vec<_>>();
+        for limit in [1, 2, 5, 100] {
+            let backbone = FastBruteForceSolver::backbone(&puzzle, Some(limit));
+            for cell in 0 .. N_CELLS {
+                for digit in 1 ..= 9 {
+                    let count = solutions.iter().filter(|solution| solution[cell] == digit).count();
+                    assert_eq!(backbone.count(cell, digit), Some(count.min(limit)));
+                }
+            }
+        }
+    }
+}
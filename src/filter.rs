@@ -1,47 +1,154 @@
 use std::collections::HashSet;
+use std::sync::Mutex;
+
+use itertools::Itertools;
 
 use crate::fast_solver::FastBruteForceSolver;
-use crate::logic::BasicSolver;
+use crate::logic::{chain_techniques, uniqueness_techniques, BasicSolver, Technique, Tier};
 use crate::minlex::minlex;
-use crate::pipeline::RegionMaskedSudoku;
-use crate::sudoku::Sudoku;
+use crate::pipeline::{PipelineFilter, RegionMaskedSudoku};
+use crate::rating::{base_difficulty, rate};
+use crate::sudoku::{parse_cell, Sudoku};
 
+/// A test that puzzles must pass to continue along a pipeline.
+///
+/// Filters may be combined with `Not`, `Any` and `All`. These try their inner filters
+/// in order and stop as soon as the outcome is known, so a stateful filter such as
+/// `NonEquivalent` only sees the puzzles which reach it. It records every puzzle it
+/// sees, whatever the combinator then makes of its answer: within `Not` it passes
+/// only puzzles equivalent to one seen before, and within `Any` a puzzle passed by an
+/// earlier filter is never recorded, so an equivalent one may still pass later.
 pub enum Filter {
     AtMostNBasicPlacements { n: usize },
     SolvesWithBasicsAfterElims { elims: Vec<((usize, usize), u8)> },
     HasAnySolution,
     HasUniqueSolution,
-    NonEquivalent { seen_minlexes: HashSet<Sudoku> },
+    /// Has a unique solution, which removing any one of the clues would lose
+    IsMinimal,
+    NonEquivalent { seen_minlexes: Mutex<HashSet<Sudoku>> },
+    /// Solves using the techniques of the given tier and those below it. If the
+    /// tier is required then the puzzle must also not solve without the tier, so
+    /// `tier=fish tier_required=true` accepts puzzles needing at least one fish,
+    /// while `tier=basics` forbids fish and everything harder. The uniqueness
+    /// techniques may be forbidden, allowed or required in the same way.
+    SolvesWith { tier: Tier, tier_required: bool, uniqueness: Uniqueness },
+    /// Doesn't solve with wings or anything easier, but does once chains of
+    /// at most the given number of nodes are allowed as well
+    RequiresChain { max_length: usize },
+    /// Has an ER rating within the given range, inclusive. Unrated puzzles never match.
+    RatingBetween { min: f64, max: f64 },
+    /// Solves using only the allowed techniques, applied one deduction at a time and
+    /// always using the easiest one that makes progress, with every required
+    /// technique being used somewhere along the way
+    UsesTechniques { allowed: Vec<Technique>, required: Vec<Technique> },
+    /// Passes the puzzles which the inner filter rejects
+    Not(Box<dyn PipelineFilter>),
+    /// Passes the puzzles which any of the inner filters pass
+    Any(Vec<Box<dyn PipelineFilter>>),
+    /// Passes the puzzles which all of the inner filters pass
+    All(Vec<Box<dyn PipelineFilter>>),
 }
 
-impl Filter {
-    pub fn matches(&mut self, sudoku: &RegionMaskedSudoku) -> bool {
+/// Whether a filter which solves puzzles may use the uniqueness techniques
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Uniqueness {
+    Forbidden,
+    Allowed,
+    /// The puzzle must not solve without them
+    Required,
+}
+
+impl PipelineFilter for Filter {
+    fn matches(&self, sudoku: &RegionMaskedSudoku) -> bool {
         match self {
             Self::AtMostNBasicPlacements { n } => at_most_n_basic_placements(*n, sudoku),
             Self::SolvesWithBasicsAfterElims { elims } => solves_with_basics_after_elims(elims, sudoku),
             Self::HasAnySolution => FastBruteForceSolver::has_solution(sudoku.sudoku()),
             Self::HasUniqueSolution => FastBruteForceSolver::has_unique_solution(sudoku.sudoku()),
+            Self::IsMinimal => FastBruteForceSolver::has_unique_solution(sudoku.sudoku()) && FastBruteForceSolver::is_minimal(sudoku.sudoku()),
             Self::NonEquivalent { seen_minlexes } => non_equivalent(sudoku.sudoku(), seen_minlexes),
+            Self::SolvesWith { tier, tier_required, uniqueness } => solves_with(*tier, *tier_required, *uniqueness, sudoku),
+            Self::RequiresChain { max_length } => requires_chain(*max_length, sudoku),
+            Self::UsesTechniques { allowed, required } => uses_techniques(allowed, required, sudoku),
+            Self::RatingBetween { min, max } => rate(sudoku).is_some_and(|rating| (*min ..= *max).contains(&rating.er)),
+            Self::Not(filter) => !filter.matches(sudoku),
+            Self::Any(filters) => filters.iter().any(|filter| filter.matches(sudoku)),
+            Self::All(filters) => filters.iter().all(|filter| filter.matches(sudoku)),
         }
     }
 
+    /// The entries of inner filters are prefixed with their index, e.g. `1:<entry>`
+    fn save_state(&self) -> Vec<String> {
+        match self {
+            Self::NonEquivalent { seen_minlexes } => seen_minlexes.lock().unwrap().iter().map(|sudoku| sudoku.to_string()).collect(),
+            Self::Not(filter) => filter.save_state().into_iter().map(|entry| format!("0:{}", entry)).collect(),
+            Self::Any(filters) | Self::All(filters) => filters.iter().enumerate()
+                .flat_map(|(idx, filter)| filter.save_state().into_iter().map(move |entry| format!("{}:{}", idx, entry)))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn restore_state(&self, entry: &str) -> Result<(), String> {
+        match self {
+            Self::NonEquivalent { seen_minlexes } => {
+                let sudoku = Sudoku::from_str(entry).ok_or_else(|| format!("expected a puzzle, found `{}`", entry))?;
+                seen_minlexes.lock().unwrap().insert(sudoku);
+                Ok(())
+            }
+            Self::Not(_) | Self::Any(_) | Self::All(_) => {
+                let (idx, inner) = entry.split_once(':').ok_or_else(|| format!("expected an inner filter's index, found `{}`", entry))?;
+                let filter = match self {
+                    Self::Not(filter) => (idx == "0").then_some(filter),
+                    Self::Any(filters) | Self::All(filters) => idx.parse().ok().and_then(|idx: usize| filters.get(idx)),
+                    _ => unreachable!(),
+                };
+                filter.ok_or_else(|| format!("no inner filter `{}`", idx))?.restore_state(inner)
+            }
+            _ => Err("this filter has no state".to_string()),
+        }
+    }
+
+    fn depends_on_earlier_puzzles(&self) -> bool {
+        match self {
+            Self::NonEquivalent { .. } => true,
+            Self::Not(filter) => filter.depends_on_earlier_puzzles(),
+            Self::Any(filters) | Self::All(filters) => filters.iter().any(|filter| filter.depends_on_earlier_puzzles()),
+            _ => false,
+        }
+    }
+}
+
+impl Filter {
     pub fn at_most_n_basic_placements(n: usize) -> Self {
         Self::AtMostNBasicPlacements { n }
     }
 
-    pub fn solves_with_basics_after_elims(elim_str: &str) -> Self {
-        let elims = elim_str.split(",").map(|s| s.trim());
-        let elims = elims.flat_map(|elim| {
-            let (digits, rc) = elim.split_once("r").unwrap();
-            let (r, c) = rc.split_once("c").unwrap();
-            let (r, c): (usize, usize) = (r.parse().unwrap(), c.parse().unwrap());
-            digits.chars().map(|d| d.to_digit(10).unwrap() as u8).map(move |d| ((r - 1, c - 1), d))
-        });
-        Self::SolvesWithBasicsAfterElims { elims: elims.collect() }
+    pub fn solves_with_basics_after_elims(elim_str: &str) -> Result<Self, String> {
+        let mut elims = Vec::new();
+        for elim in elim_str.split(",").map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            let (digits, rc) = elim.split_at(elim.find('r').ok_or_else(|| format!("expected an elimination such as `12r3c4`, found `{}`", elim))?);
+            let (r, c) = parse_cell(rc)?;
+            if digits.is_empty() { return Err(format!("no digits to eliminate in `{}`", elim)); }
+            for d in digits.chars() {
+                match d.to_digit(10) {
+                    Some(d @ 1 ..= 9) => elims.push(((r, c), d as u8)),
+                    _ => return Err(format!("expected a digit from 1 to 9, found `{}` in `{}`", d, elim)),
+                }
+            }
+        }
+        Ok(Self::SolvesWithBasicsAfterElims { elims })
+    }
+
+    /// The required techniques are always allowed
+    pub fn uses_techniques(allowed: Vec<Technique>, required: Vec<Technique>) -> Self {
+        let mut allowed = allowed.into_iter().chain(required.iter().copied()).unique().collect::<Vec<_>>();
+        allowed.sort_by(|a, b| base_difficulty(*a).total_cmp(&base_difficulty(*b)));
+        Self::UsesTechniques { allowed, required }
     }
 
     pub fn non_equivalent() -> Self {
-        Self::NonEquivalent { seen_minlexes: HashSet::new() }
+        Self::NonEquivalent { seen_minlexes: Mutex::new(HashSet::new()) }
     }
 }
 
@@ -63,6 +170,151 @@ fn solves_with_basics_after_elims(elims: &[((usize, usize), u8)], sudoku: &Regio
     solver.is_solved()
 }
 
-fn non_equivalent(sudoku: &Sudoku, seen_minlexes: &mut HashSet<Sudoku>) -> bool {
-    seen_minlexes.insert(minlex(sudoku))
+fn solves_with(tier: Tier, tier_required: bool, uniqueness: Uniqueness, sudoku: &RegionMaskedSudoku) -> bool {
+    let allow_uniqueness = uniqueness != Uniqueness::Forbidden;
+    if tier_required && solves_with_tier(tier.previous(), allow_uniqueness, sudoku) { return false; }
+    if uniqueness == Uniqueness::Required && solves_with_tier(Some(tier), false, sudoku) { return false; }
+    solves_with_tier(Some(tier), allow_uniqueness, sudoku)
+}
+
+fn solves_with_tier(tier: Option<Tier>, uniqueness: bool, sudoku: &RegionMaskedSudoku) -> bool {
+    let mut solver = BasicSolver::for_region_masked_sudoku(sudoku);
+    if let Some(tier) = tier {
+        let mut techniques = tier.techniques();
+        if uniqueness {
+            solver.assume_uniqueness();
+            techniques.extend(uniqueness_techniques());
+        }
+        solver.solve_with(&techniques);
+    }
+    solver.is_solved()
+}
+
+fn requires_chain(max_length: usize, sudoku: &RegionMaskedSudoku) -> bool {
+    let mut techniques = Tier::Wings.techniques();
+    let mut solver = BasicSolver::for_region_masked_sudoku(sudoku);
+    solver.solve_with(&techniques);
+    if solver.is_solved() { return false; }
+    techniques.extend(chain_techniques(max_length));
+    solver.solve_with(&techniques);
+    solver.is_solved()
+}
+
+fn uses_techniques(allowed: &[Technique], required: &[Technique], sudoku: &RegionMaskedSudoku) -> bool {
+    let mut solver = BasicSolver::for_region_masked_sudoku(sudoku);
+    if allowed.iter().any(|technique| uniqueness_techniques().contains(technique)) {
+        solver.assume_uniqueness();
+    }
+    solver.enable_log();
+    while !solver.is_solved() {
+        let Some(deduction) = allowed.iter().find_map(|&technique| solver.find(technique)) else { return false; };
+        solver.apply(deduction);
+    }
+    let used = solver.take_log();
+    required.iter().all(|required| used.iter().any(|deduction| required.includes(deduction.technique)))
+}
+
+// The set of seen minlexes is shared between threads when running in parallel,
+// so only hold the lock for the insertion itself.
+fn non_equivalent(sudoku: &Sudoku, seen_minlexes: &Mutex<HashSet<Sudoku>>) -> bool {
+    let minlexed = minlex(sudoku);
+    seen_minlexes.lock().unwrap().insert(minlexed)
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::iproduct;
+
+    use super::*;
+
+    /// A puzzle which needs an X-Wing along with the basics
+    const X_WING_PUZZLE: &str = "007300000602000900000024010000000009001080000340900750089003500500000084000000003";
+
+    fn puzzle(text: &str) -> RegionMaskedSudoku {
+        RegionMaskedSudoku::from_sudoku(&Sudoku::from_str(text).unwrap())
+    }
+
+    /// A filter which gives the same answer for every puzzle
+    struct Fixed(bool);
+
+    impl PipelineFilter for Fixed {
+        fn matches(&self, _sudoku: &RegionMaskedSudoku) -> bool {
+            self.0
+        }
+    }
+
+    fn fixed(answers: &[bool]) -> Vec<Box<dyn PipelineFilter>> {
+        answers.iter().map(|&answer| Box::new(Fixed(answer)) as Box<dyn PipelineFilter>).collect()
+    }
+
+    fn basics() -> Vec<Technique> {
+        let mut techniques = vec![Technique::NakedSingle, Technique::HiddenSingle, Technique::Pointing, Technique::Claiming];
+        techniques.extend((2 ..= 4).flat_map(|size| [Technique::NakedSubset(size), Technique::HiddenSubset(size)]));
+        techniques
+    }
+
+    #[test]
+    fn uses_techniques() {
+        let sudoku = puzzle(X_WING_PUZZLE);
+        let with_x_wing = basics().into_iter().chain([Technique::Fish(2)]).collect::<Vec<_>>();
+        assert!(!Filter::uses_techniques(basics(), Vec::new()).matches(&sudoku));
+        assert!(Filter::uses_techniques(with_x_wing.clone(), Vec::new()).matches(&sudoku));
+
+        // Required techniques are allowed, but must also be used
+        assert!(Filter::uses_techniques(basics(), vec![Technique::Fish(2)]).matches(&sudoku));
+        assert!(!Filter::uses_techniques(with_x_wing, vec![Technique::Skyscraper]).matches(&sudoku));
+    }
+
+    #[test]
+    fn is_minimal() {
+        let minimal = "000290000087605000100007000000000407243000100000000009038000010020400900000089503";
+        assert!(Filter::IsMinimal.matches(&puzzle(minimal)));
+
+        // Without r5c7 the puzzle has several solutions, though removing any other clue still adds more
+        let several_solutions = format!("{}0{}", &minimal[.. 42], &minimal[43 ..]);
+        assert!(FastBruteForceSolver::is_minimal(&Sudoku::from_str(&several_solutions).unwrap()));
+        assert!(!Filter::IsMinimal.matches(&puzzle(&several_solutions)));
+    }
+
+    #[test]
+    fn combinators() {
+        let sudoku = puzzle(X_WING_PUZZLE);
+        for answer in [false, true] {
+            assert_eq!(Filter::Not(Box::new(Fixed(answer))).matches(&sudoku), !answer);
+        }
+        for (a, b) in iproduct!([false, true], [false, true]) {
+            assert_eq!(Filter::Any(fixed(&[a, b])).matches(&sudoku), a || b);
+            assert_eq!(Filter::All(fixed(&[a, b])).matches(&sudoku), a && b);
+        }
+
+        // Recipes can't give a combinator no inner filters, but it can still be built directly
+        assert!(!Filter::Any(Vec::new()).matches(&sudoku));
+        assert!(Filter::All(Vec::new()).matches(&sudoku));
+    }
+
+    #[test]
+    fn stateful_filters_within_combinators() {
+        let sudoku = puzzle(X_WING_PUZZLE);
+        let minlexed = minlex(sudoku.sudoku());
+
+        // The inner filter records the puzzle even though `Not` rejects it
+        let filter = Filter::Not(Box::new(Filter::non_equivalent()));
+        assert!(!filter.matches(&sudoku));
+        assert_eq!(filter.save_state(), [format!("0:{}", minlexed)]);
+        assert!(filter.matches(&sudoku));
+
+        // An inner filter which is never reached records nothing
+        let mut filters = fixed(&[true]);
+        filters.push(Box::new(Filter::non_equivalent()));
+        let filter = Filter::Any(filters);
+        assert!(filter.matches(&sudoku));
+        assert!(filter.save_state().is_empty());
+
+        let mut filters = fixed(&[false]);
+        filters.push(Box::new(Filter::non_equivalent()));
+        let filter = Filter::Any(filters);
+        assert!(filter.matches(&sudoku));
+        assert_eq!(filter.save_state(), [format!("1:{}", minlexed)]);
+        assert!(!filter.matches(&sudoku));
+    }
 }
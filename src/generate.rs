@@ -1,20 +1,139 @@
 use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
 use std::iter::empty;
+use std::ops::Range;
+use std::path::PathBuf;
 use std::rc::Rc;
 
+use itertools::Itertools;
+
 use crate::bitmask::{BitIter, Bitmask};
-use crate::dfs_with_progress::{DepthFirstSearcherWithProgress, DepthFirstTraversable};
-use crate::pipeline::RegionMaskedSudoku;
+use crate::fast_solver::FastBruteForceSolver;
+use crate::logic::ALL_CELLS;
+use crate::minlex::{is_least_under, pattern_automorphisms};
+use crate::dfs_with_progress::{DepthFirstSearcherWithProgress, DepthFirstTraversable, ResumableSearch, SearchPosition, Subtree};
+use crate::pipeline::{PipelineItem, PipelineSearch, RegionMaskedSudoku};
+use crate::random::{uniform_solution_grid, Rng};
+use crate::sudoku::Sudoku;
+use crate::symmetry::DihedralSubgroup;
 use crate::template::{Template, TemplateDigit};
 
+#[allow(clippy::large_enum_variant)]
 pub enum GenerationBase {
     Template(Template),
+    /// The puzzles read from a file, or from stdin if there is no path, one per line
+    /// using the first 81 characters of each line, which must be digits or `.`s.
+    /// Lines which are not puzzles, such as headers and comments, are skipped. A
+    /// file is split into jobs by byte ranges, while stdin is read as a single job,
+    /// and can't be resumed from a checkpoint.
+    PuzzleList { path: Option<PathBuf>, length: u64 },
+    /// Every essentially different way of filling a pattern of cells with digits so
+    /// that the puzzle has a unique solution. Of the fillings which are the same up
+    /// to relabelling the digits and the symmetries mapping the pattern onto itself,
+    /// only the least is given.
+    Pattern { cells: Bitmask<u128>, automorphisms: Vec<[u8; 81]> },
+    /// Every puzzle with a unique solution made of `clue_count` clues of the given
+    /// solution grid, whose clues are a union of orbits of the symmetry. Each puzzle
+    /// must have a clue in each of the grid's unavoidable sets, which are found once
+    /// up front rather than by every job.
+    SolutionGrid { grid: Sudoku, clue_count: usize, symmetry: DihedralSubgroup, unavoidable_sets: Vec<Bitmask<u128>> },
+    /// An endless stream of random minimal puzzles, each made by removing the clues
+    /// of random orbits of the symmetry from a uniformly random solution grid for as
+    /// long as the solution stays unique. The seed is split into `RANDOM_STREAMS`
+    /// streams, and every puzzle comes from its own random numbers, worked out from
+    /// the seed, its stream and its index within the stream. Each job takes an equal
+    /// share of the streams in turn, so the puzzles depend on the seed alone, while
+    /// the number of threads and shards only changes which of them are made first.
+    Random { seed: u64, symmetry: DihedralSubgroup },
+    /// An endless stream of solution grids, each chosen uniformly at random from all
+    /// of the solution grids. Like `Random`, the grids come from the streams of the
+    /// seed, and depend on the seed alone.
+    RandomGrids { seed: u64 },
 }
 
 impl GenerationBase {
-    pub fn iter(&self) -> Box<dyn Iterator<Item = (f64, f64, Rc<RefCell<RegionMaskedSudoku>>)>> {
+
+    /// Fill the given pattern, in which `.` and `0` are empty cells, whitespace is
+    /// ignored, and anything else is a clue
+    pub fn pattern(pattern: &str) -> Result<Self, String> {
+        let cells = pattern.chars().filter(|c| !c.is_whitespace()).map(|c| c != '.' && c != '0').collect::<Vec<_>>();
+        if cells.len() != 81 { return Err(format!("expected 81 cells, found {}", cells.len())); }
+        let cells = Bitmask::<u128>::from_iter((0 .. 81).filter(|&cell| cells[cell]));
+        Ok(Self::Pattern { cells, automorphisms: pattern_automorphisms(cells) })
+    }
+
+    /// Search the given solution grid for its puzzles with `clue_count` clues
+    pub fn solution_grid(grid: Sudoku, clue_count: usize, symmetry: DihedralSubgroup) -> Result<Self, String> {
+        if grid.digits().any(|&digit| digit == 0) || !FastBruteForceSolver::has_solution(&grid) {
+            return Err("not a valid solution grid".to_string());
+        }
+        if clue_count > 81 { return Err(format!("a puzzle can't have {} clues", clue_count)); }
+        let unavoidable_sets = unavoidable_sets(&grid);
+        Ok(Self::SolutionGrid { grid, clue_count, symmetry, unavoidable_sets })
+    }
+
+    /// Read puzzles from the given file, or from stdin if there is no path
+    pub fn puzzle_list(path: Option<PathBuf>) -> Result<Self, String> {
+        let length = match &path {
+            Some(path) => File::open(path).and_then(|file| file.metadata()).map_err(|err| format!("{}: {}", path.display(), err))?.len(),
+            None => 0,
+        };
+        Ok(Self::PuzzleList { path, length })
+    }
+
+    /// Whether the search goes on forever, rather than finishing
+    pub fn is_endless(&self) -> bool {
+        matches!(self, Self::Random { .. } | Self::RandomGrids { .. })
+    }
+
+    pub fn iter(&self) -> Result<PipelineSearch, String> {
+        self.iter_subtree(Subtree::root())
+    }
+
+    /// Iterate over only the given subtree of the search, as returned by `subtrees`
+    pub fn iter_subtree(&self, subtree: Subtree) -> Result<PipelineSearch, String> {
+        match self {
+            Self::Template(template) => Ok(Box::new(DepthFirstSearcherWithProgress::for_subtree(TemplateGeneratorState::for_template(template), subtree))),
+            Self::PuzzleList { path, length } => Ok(Box::new(PuzzleListSearch::for_subtree(path.as_ref(), *length, subtree)?)),
+            Self::Pattern { cells, automorphisms } => Ok(Box::new(DepthFirstSearcherWithProgress::for_subtree(PatternGeneratorState::for_pattern(*cells, automorphisms), subtree))),
+            Self::SolutionGrid { grid, clue_count, symmetry, unavoidable_sets } => Ok(Box::new(DepthFirstSearcherWithProgress::for_subtree(SolutionGridSearchState::for_grid(grid, *clue_count, *symmetry, unavoidable_sets), subtree))),
+            Self::Random { seed, symmetry } => Ok(Box::new(RandomSearch::for_subtree(*seed, RandomItems::MinimalPuzzles(*symmetry), subtree))),
+            Self::RandomGrids { seed } => Ok(Box::new(RandomSearch::for_subtree(*seed, RandomItems::SolutionGrids, subtree))),
+        }
+    }
+
+    /// Resume iterating over the given subtree of the search from a saved position
+    pub fn resume_subtree(&self, subtree: Subtree, position: &SearchPosition) -> Result<PipelineSearch, String> {
+        match self {
+            Self::Template(template) => Ok(Box::new(DepthFirstSearcherWithProgress::resume(TemplateGeneratorState::for_template(template), subtree, position)?)),
+            Self::Pattern { cells, automorphisms } => Ok(Box::new(DepthFirstSearcherWithProgress::resume(PatternGeneratorState::for_pattern(*cells, automorphisms), subtree, position)?)),
+            Self::SolutionGrid { grid, clue_count, symmetry, unavoidable_sets } => Ok(Box::new(DepthFirstSearcherWithProgress::resume(SolutionGridSearchState::for_grid(grid, *clue_count, *symmetry, unavoidable_sets), subtree, position)?)),
+            Self::Random { seed, symmetry } => Ok(Box::new(RandomSearch::resume(*seed, RandomItems::MinimalPuzzles(*symmetry), subtree, position)?)),
+            Self::RandomGrids { seed } => Ok(Box::new(RandomSearch::resume(*seed, RandomItems::SolutionGrids, subtree, position)?)),
+            Self::PuzzleList { path: None, .. } => Err("puzzles read from stdin can't be resumed".to_string()),
+            Self::PuzzleList { path, length } => {
+                let mut search = PuzzleListSearch::for_subtree(path.as_ref(), *length, subtree)?;
+                search.skip_lines(position)?;
+                Ok(Box::new(search))
+            }
+        }
+    }
+
+    /// Split the search into at least `min_count` disjoint subtrees if possible
+    pub fn subtrees(&self, min_count: usize) -> Vec<Subtree> {
         match self {
-            Self::Template(template) => Box::new(DepthFirstSearcherWithProgress::new(TemplateGeneratorState::for_template(template))),
+            Self::Template(template) => Subtree::split(&mut TemplateGeneratorState::for_template(template), min_count),
+            Self::Pattern { cells, automorphisms } => Subtree::split(&mut PatternGeneratorState::for_pattern(*cells, automorphisms), min_count),
+            Self::SolutionGrid { grid, clue_count, symmetry, unavoidable_sets } => Subtree::split(&mut SolutionGridSearchState::for_grid(grid, *clue_count, *symmetry, unavoidable_sets), min_count),
+            Self::Random { .. } | Self::RandomGrids { .. } => {
+                let size = 1.0 / min_count as f64;
+                (0 .. min_count).map(|idx| Subtree { path: vec![idx], start: idx as f64 * size, size, descend: true }).collect()
+            }
+            Self::PuzzleList { path: None, .. } => vec![Subtree::root()],
+            Self::PuzzleList { .. } => (0 .. min_count).map(|idx| {
+                Subtree { path: vec![idx], start: idx as f64 / min_count as f64, size: 1.0 / min_count as f64, descend: true }
+            }).collect(),
         }
     }
 }
@@ -83,3 +202,521 @@ impl DepthFirstTraversable for TemplateGeneratorState {
         (self.placement_count == self.wildcards.len()).then(|| self.sudoku.clone())
     }
 }
+
+/// A structure capable of iterating over the essentially different fillings of a
+/// pattern. Cells are filled in order, with each digit being at most one more than
+/// the largest before it, so that only the least relabelling of each is reached.
+struct PatternGeneratorState {
+    sudoku: Rc<RefCell<RegionMaskedSudoku>>,
+    cells: Vec<usize>,
+    automorphisms: Vec<[u8; 81]>,
+    /// The largest digit placed so far, after each placement
+    largest: Vec<u8>,
+}
+
+impl PatternGeneratorState {
+    pub fn for_pattern(cells: Bitmask<u128>, automorphisms: &[[u8; 81]]) -> Self {
+        Self {
+            sudoku: Rc::new(RefCell::new(RegionMaskedSudoku::empty())),
+            cells: cells.as_bit_iter().collect(),
+            automorphisms: automorphisms.to_vec(),
+            largest: Vec::new(),
+        }
+    }
+}
+
+impl DepthFirstTraversable for PatternGeneratorState {
+    type Step = (usize, u8);
+    type Output = Rc<RefCell<RegionMaskedSudoku>>;
+
+    fn next_steps(&mut self) -> Box<dyn ExactSizeIterator<Item = Self::Step>> {
+        let Some(&idx) = self.cells.get(self.largest.len()) else { return Box::new(empty()); };
+        let largest = self.largest.last().copied().unwrap_or(0) as usize;
+        let digits = self.sudoku.borrow().candidates(idx) & Bitmask::<u16>::from_iter(1 ..= (largest + 1).min(9));
+        Box::new(digits.as_bit_iter().map(move |d| (idx, d as u8)))
+    }
+
+    fn apply_step(&mut self, &(idx, d): &Self::Step) {
+        self.sudoku.borrow_mut().place(idx, d);
+        self.largest.push(self.largest.last().copied().unwrap_or(0).max(d));
+    }
+
+    fn revert_step(&mut self, &(idx, d): &Self::Step) {
+        self.sudoku.borrow_mut().unplace(idx, d);
+        self.largest.pop();
+    }
+
+    fn should_prune(&mut self) -> bool {
+        !FastBruteForceSolver::has_solution(self.sudoku.borrow().sudoku())
+    }
+
+    fn output(&mut self) -> Option<Self::Output> {
+        let sudoku = self.sudoku.borrow();
+        let is_output = self.largest.len() == self.cells.len()
+            && FastBruteForceSolver::has_unique_solution(sudoku.sudoku())
+            && is_least_under(sudoku.sudoku(), &self.automorphisms);
+        is_output.then(|| self.sudoku.clone())
+    }
+}
+
+/// A structure capable of iterating over the sets of clues of a solution grid that
+/// make puzzles with a unique solution. Every such set must contain a clue from each
+/// unavoidable set of the grid, so rather than trying every orbit the search tries
+/// each orbit meeting an unavoidable set not yet hit, ruling out the orbits tried
+/// before it so that no set of clues is reached twice.
+struct SolutionGridSearchState {
+    sudoku: Rc<RefCell<RegionMaskedSudoku>>,
+    grid: Sudoku,
+    clue_count: usize,
+    orbits: Vec<Bitmask<u128>>,
+    /// The index in `orbits` of the orbit of each cell
+    orbit_indices: [usize; 81],
+    unavoidable_sets: Vec<Bitmask<u128>>,
+    clues: Bitmask<u128>,
+    /// The cells of the orbits which have been ruled out
+    excluded: Bitmask<u128>,
+}
+
+impl SolutionGridSearchState {
+    pub fn for_grid(grid: &Sudoku, clue_count: usize, symmetry: DihedralSubgroup, unavoidable_sets: &[Bitmask<u128>]) -> Self {
+        let orbits = (0 .. 81)
+            .map(|cell| Bitmask::<u128>::from_iter(symmetry.orbits()[cell].iter().copied()))
+            .unique_by(|orbit| orbit.as_bit_iter().next())
+            .collect::<Vec<_>>();
+        let orbit_indices = std::array::from_fn(|cell| orbits.iter().position(|orbit| orbit.contains(cell)).unwrap());
+        Self {
+            sudoku: Rc::new(RefCell::new(RegionMaskedSudoku::empty())),
+            grid: grid.clone(),
+            clue_count,
+            orbits,
+            orbit_indices,
+            unavoidable_sets: unavoidable_sets.to_vec(),
+            clues: Bitmask::<u128>::empty(),
+            excluded: Bitmask::<u128>::empty(),
+        }
+    }
+
+    /// The cells which may still become clues
+    fn allowed(&self) -> Bitmask<u128> {
+        ALL_CELLS & !self.clues & !self.excluded
+    }
+
+    /// The orbits which may still be added without going over the clue count
+    fn fitting_orbits(&self, cells: Bitmask<u128>) -> Vec<usize> {
+        let remaining = self.clue_count - self.clues.count_ones() as usize;
+        cells.as_bit_iter().map(|cell| self.orbit_indices[cell]).unique()
+            .filter(|&idx| self.orbits[idx].count_ones() as usize <= remaining)
+            .collect()
+    }
+}
+
+impl DepthFirstTraversable for SolutionGridSearchState {
+    /// The orbit to add, and the orbits tried before it which it rules out
+    type Step = (usize, Bitmask<u128>);
+    type Output = Rc<RefCell<RegionMaskedSudoku>>;
+
+    fn next_steps(&mut self) -> Box<dyn ExactSizeIterator<Item = Self::Step>> {
+        if self.clues.count_ones() as usize == self.clue_count { return Box::new(empty()); }
+        // Branch on the unhit unavoidable set with the fewest ways to hit it, or if
+        // they are all hit, on every orbit that is left
+        let allowed = self.allowed();
+        let orbits = self.unavoidable_sets.iter()
+            .filter(|&&set| (set & self.clues).is_empty())
+            .map(|&set| self.fitting_orbits(set & allowed))
+            .min_by_key(|orbits| orbits.len())
+            .unwrap_or_else(|| self.fitting_orbits(allowed));
+        let mut tried = Bitmask::<u128>::empty();
+        let steps = orbits.into_iter().map(|idx| {
+            let step = (idx, tried);
+            tried |= self.orbits[idx];
+            step
+        }).collect::<Vec<_>>();
+        Box::new(steps.into_iter())
+    }
+
+    fn apply_step(&mut self, &(idx, tried): &Self::Step) {
+        let mut sudoku = self.sudoku.borrow_mut();
+        self.orbits[idx].as_bit_iter().for_each(|cell| sudoku.place(cell, self.grid[cell]));
+        self.clues |= self.orbits[idx];
+        self.excluded |= tried;
+    }
+
+    fn revert_step(&mut self, &(idx, tried): &Self::Step) {
+        let mut sudoku = self.sudoku.borrow_mut();
+        self.orbits[idx].as_bit_iter().for_each(|cell| sudoku.unplace(cell, self.grid[cell]));
+        self.clues ^= self.orbits[idx];
+        self.excluded ^= tried;
+    }
+
+    fn should_prune(&mut self) -> bool {
+        let allowed = self.allowed();
+        let remaining = self.clue_count - self.clues.count_ones() as usize;
+        if (allowed.count_ones() as usize) < remaining { return true; }
+        // Each unhit unavoidable set needs a clue of its own if they are disjoint
+        let mut disjoint = Bitmask::<u128>::empty();
+        let mut needed = 0;
+        for &set in self.unavoidable_sets.iter().filter(|&&set| (set & self.clues).is_empty()) {
+            if (set & allowed).is_empty() { return true; }
+            if (set & disjoint).is_empty() {
+                disjoint |= set;
+                needed += 1;
+                if needed > remaining { return true; }
+            }
+        }
+        false
+    }
+
+    fn output(&mut self) -> Option<Self::Output> {
+        let is_output = self.clues.count_ones() as usize == self.clue_count
+            && self.unavoidable_sets.iter().all(|&set| (set & self.clues).is_not_empty())
+            && FastBruteForceSolver::has_unique_solution(self.sudoku.borrow().sudoku());
+        is_output.then(|| self.sudoku.clone())
+    }
+}
+
+/// The number of other solutions to look at when finding each batch of unavoidable sets
+const UNAVOIDABLE_SET_SOLUTION_LIMIT: usize = 1000;
+
+/// Some of the minimal unavoidable sets of a solution grid, smallest first: sets
+/// of cells which could be filled in another way, so that any puzzle with a unique
+/// solution has a clue in each. They are found by emptying the cells with two or three
+/// of the digits, and each band and stack, and comparing the other solutions with
+/// the grid.
+fn unavoidable_sets(grid: &Sudoku) -> Vec<Bitmask<u128>> {
+    let digit_groups = (1 ..= 9).combinations(2).chain((1 ..= 9).combinations(3))
+        .map(|digits| Bitmask::<u128>::from_iter((0 .. 81).filter(|&cell| digits.contains(&grid[cell]))));
+    let bands_and_stacks = (0 .. 3)
+        .map(|band| Bitmask::<u128>::from_iter((0 .. 81).filter(|&cell| cell / 27 == band)))
+        .chain((0 .. 3).map(|stack| Bitmask::<u128>::from_iter((0 .. 81).filter(|&cell| cell % 9 / 3 == stack))));
+
+    let mut sets = Vec::new();
+    for emptied in digit_groups.chain(bands_and_stacks) {
+        let mut puzzle = grid.clone();
+        emptied.as_bit_iter().for_each(|cell| puzzle[cell] = 0);
+        for solution in FastBruteForceSolver::solutions(&puzzle).take(UNAVOIDABLE_SET_SOLUTION_LIMIT) {
+            let differences = Bitmask::<u128>::from_iter((0 .. 81).filter(|&cell| solution[cell] != grid[cell]));
+            if differences.is_not_empty() { sets.push(differences); }
+        }
+    }
+
+    // Only the minimal sets are worth keeping
+    sets.sort_by_key(|set| set.count_ones());
+    let mut minimal: Vec<Bitmask<u128>> = Vec::new();
+    for set in sets {
+        if minimal.iter().all(|&other| (other & !set).is_not_empty()) { minimal.push(set); }
+    }
+    minimal
+}
+
+/// How many streams the puzzles of a random search are split between. The jobs
+/// of a run take equal shares of these, however many of them there are.
+const RANDOM_STREAMS: u64 = 1 << 16;
+
+/// What a `RandomSearch` makes
+#[derive(Clone, Copy)]
+enum RandomItems {
+    MinimalPuzzles(DihedralSubgroup),
+    SolutionGrids,
+}
+
+/// An endless stream of random puzzles or grids for a single job, taking one from
+/// each of its streams in turn
+struct RandomSearch {
+    seed: u64,
+    items: RandomItems,
+    subtree: Subtree,
+    streams: Range<u64>,
+    generated: usize,
+    current: Option<Rc<RefCell<RegionMaskedSudoku>>>,
+}
+
+impl RandomSearch {
+    fn for_subtree(seed: u64, items: RandomItems, subtree: Subtree) -> Self {
+        // The subtrees from `subtrees` each take an equal share of the streams
+        let streams = match subtree.path.first() {
+            Some(&idx) => {
+                let count = (1.0 / subtree.size).round() as u64;
+                idx as u64 * RANDOM_STREAMS / count .. (idx as u64 + 1) * RANDOM_STREAMS / count
+            }
+            None => 0 .. RANDOM_STREAMS,
+        };
+        Self { seed, items, subtree, streams, generated: 0, current: None }
+    }
+
+    fn resume(seed: u64, items: RandomItems, subtree: Subtree, position: &SearchPosition) -> Result<Self, String> {
+        let &[generated] = position.levels.as_slice() else { return Err("the saved position does not belong to this search".to_string()); };
+        let mut search = Self::for_subtree(seed, items, subtree);
+        // The puzzle that was current when the position was saved is made again
+        if generated > 0 {
+            search.generated = generated - 1;
+            search.generate();
+        }
+        Ok(search)
+    }
+
+    /// Make the next puzzle or grid of the job, from its own random numbers
+    fn generate(&mut self) -> Rc<RefCell<RegionMaskedSudoku>> {
+        let (generated, width) = (self.generated as u64, self.streams.end - self.streams.start);
+        let mut rng = Rng::from_seeds(&[self.seed, self.streams.start + generated % width, generated / width]);
+        self.generated += 1;
+        let sudoku = match self.items {
+            RandomItems::MinimalPuzzles(symmetry) => random_minimal_puzzle(&mut rng, symmetry),
+            RandomItems::SolutionGrids => uniform_solution_grid(&mut rng),
+        };
+        let sudoku = Rc::new(RefCell::new(RegionMaskedSudoku::from_sudoku(&sudoku)));
+        self.current = Some(sudoku.clone());
+        sudoku
+    }
+}
+
+impl Iterator for RandomSearch {
+    type Item = PipelineItem;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // A run split into more jobs than there are streams leaves some of them without any
+        if self.streams.is_empty() { return None; }
+        Some((self.subtree.start, 0.0, self.generate()))
+    }
+}
+
+impl ResumableSearch for RandomSearch {
+    type Output = Rc<RefCell<RegionMaskedSudoku>>;
+
+    fn save_position(&self) -> SearchPosition {
+        SearchPosition { started: self.generated > 0, progress: 0.0, levels: vec![self.generated] }
+    }
+
+    fn current_output(&mut self) -> Option<Self::Output> {
+        self.current.clone()
+    }
+}
+
+/// A random minimal puzzle whose clues are a union of orbits of the symmetry. The
+/// orbits of a uniformly random solution grid are tried in a random order, and each is removed
+/// if the solution stays unique without it. Removing clues can only make the others
+/// more needed, so once every orbit has been tried none of those left can be removed.
+pub fn random_minimal_puzzle(rng: &mut Rng, symmetry: DihedralSubgroup) -> Sudoku {
+    let mut sudoku = uniform_solution_grid(rng);
+    let mut orbits = (0 .. 81).map(|cell| symmetry.orbits()[cell]).unique_by(|orbit| orbit.iter().min()).collect::<Vec<_>>();
+    rng.shuffle(&mut orbits);
+    for orbit in orbits {
+        let removed = orbit.iter().map(|&cell| (cell, sudoku[cell])).collect::<Vec<_>>();
+        orbit.iter().for_each(|&cell| sudoku[cell] = 0);
+        if !FastBruteForceSolver::has_unique_solution(&sudoku) {
+            removed.into_iter().for_each(|(cell, digit)| sudoku[cell] = digit);
+        }
+    }
+    sudoku
+}
+
+/// The puzzles on the lines of a file which start within a range of bytes, or on
+/// the lines of stdin. A line belongs to the range containing its first byte.
+struct PuzzleListSearch {
+    reader: Box<dyn BufRead>,
+    /// The range of bytes being read, unless reading from stdin
+    range: Option<Range<u64>>,
+    position: u64,
+    lines_read: usize,
+    subtree: Subtree,
+    current: Option<Rc<RefCell<RegionMaskedSudoku>>>,
+}
+
+impl PuzzleListSearch {
+    fn for_subtree(path: Option<&PathBuf>, length: u64, subtree: Subtree) -> Result<Self, String> {
+        let Some(path) = path else {
+            return Ok(Self { reader: Box::new(io::stdin().lock()), range: None, position: 0, lines_read: 0, subtree, current: None });
+        };
+        // The subtrees from `subtrees` each take an equal share of the file
+        let range = match subtree.path.first() {
+            Some(&idx) => {
+                let count = (1.0 / subtree.size).round() as u64;
+                idx as u64 * length / count .. (idx as u64 + 1) * length / count
+            }
+            None => 0 .. length,
+        };
+        let error = |err: io::Error| format!("{}: {}", path.display(), err);
+        let mut reader = BufReader::new(File::open(path).map_err(error)?);
+        let mut position = range.start;
+        if range.start > 0 {
+            // Skip the rest of the line that starts before the range, if any
+            reader.seek(SeekFrom::Start(range.start - 1)).map_err(error)?;
+            position += reader.skip_until(b'\n').map_err(error)? as u64 - 1;
+        }
+        Ok(Self { reader: Box::new(reader), range: Some(range), position, lines_read: 0, subtree, current: None })
+    }
+
+    /// Skip over the lines read before the given position was saved
+    fn skip_lines(&mut self, position: &SearchPosition) -> Result<(), String> {
+        let mismatch = || "the saved position does not belong to this search".to_string();
+        let &[lines_read] = position.levels.as_slice() else { return Err(mismatch()); };
+        while self.lines_read < lines_read {
+            if self.next_line().is_none() { return Err(mismatch()); }
+        }
+        Ok(())
+    }
+
+    /// Read the next line in the range, setting the current puzzle if it is one,
+    /// and returning the number of bytes read
+    fn next_line(&mut self) -> Option<u64> {
+        if self.range.as_ref().is_some_and(|range| self.position >= range.end) { return None; }
+        let mut line = String::new();
+        let bytes = self.reader.read_line(&mut line).ok().filter(|&bytes| bytes > 0)? as u64;
+        self.position += bytes;
+        self.lines_read += 1;
+        self.current = Sudoku::from_line(&line).map(|sudoku| Rc::new(RefCell::new(RegionMaskedSudoku::from_sudoku(&sudoku))));
+        Some(bytes)
+    }
+
+    /// The fraction of the range read so far, which is unknown when reading from stdin
+    fn fraction(&self, position: u64) -> f64 {
+        match &self.range {
+            Some(range) if !range.is_empty() => ((position - range.start) as f64 / (range.end - range.start) as f64).min(1.0),
+            _ => 0.0,
+        }
+    }
+}
+
+impl Iterator for PuzzleListSearch {
+    type Item = PipelineItem;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let bytes = self.next_line()?;
+            if let Some(sudoku) = &self.current {
+                let fraction = self.fraction(self.position);
+                let scale = fraction - self.fraction(self.position - bytes);
+                return Some((self.subtree.start + fraction * self.subtree.size, scale * self.subtree.size, sudoku.clone()));
+            }
+        }
+    }
+}
+
+impl ResumableSearch for PuzzleListSearch {
+    type Output = Rc<RefCell<RegionMaskedSudoku>>;
+
+    fn save_position(&self) -> SearchPosition {
+        SearchPosition { started: self.lines_read > 0, progress: self.fraction(self.position), levels: vec![self.lines_read] }
+    }
+
+    fn current_output(&mut self) -> Option<Self::Output> {
+        self.current.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::minlex::minlex;
+
+    /// The first few sudokus from the search of a base
+    fn first_sudokus(base: &GenerationBase, count: usize) -> Vec<Sudoku> {
+        base.iter().unwrap().take(count).map(|(_, _, sudoku)| sudoku.borrow().sudoku().clone()).collect()
+    }
+
+    #[test]
+    fn pattern_fillings_are_least_and_inequivalent() {
+        let base = GenerationBase::pattern("
+            xxx.x.xxx
+            xx..x..xx
+            x.x.x.x.x
+            ...xxx...
+            xxxx.xxxx
+            ...xxx...
+            x.x.x.x.x
+            xx..x..xx
+            xxx.x.xxx
+        ").unwrap();
+        let GenerationBase::Pattern { cells, automorphisms } = &base else { unreachable!() };
+        // The pattern can be rotated and reflected onto itself at least
+        assert!(automorphisms.len() >= 8);
+
+        let puzzles = first_sudokus(&base, 200);
+        assert_eq!(puzzles.len(), 200);
+        let mut minlexes = HashSet::new();
+        for puzzle in &puzzles {
+            assert!((0 .. 81).all(|cell| (puzzle[cell] != 0) == cells.contains(cell)));
+            assert!(FastBruteForceSolver::has_unique_solution(puzzle));
+            assert!(is_least_under(puzzle, automorphisms));
+            assert!(minlexes.insert(minlex(puzzle)));
+        }
+    }
+
+    #[test]
+    fn solution_grid_search_finds_every_puzzle() {
+        let grid = Sudoku::from_str("417369825632158947958724316825437169791586432346912758289643571573291684164875293").unwrap();
+        let symmetry = DihedralSubgroup::FullSymm;
+        let orbits = (0 .. 81).map(|cell| symmetry.orbits()[cell]).unique_by(|orbit| orbit.iter().min()).collect::<Vec<_>>();
+        for clue_count in [32, 36] {
+            // Try every union of orbits with the right number of clues
+            let expected = (0_u32 .. 1 << orbits.len()).filter_map(|chosen| {
+                let cells = (0 .. orbits.len()).filter(|idx| chosen & (1 << idx) != 0).flat_map(|idx| orbits[idx].iter().copied()).collect::<Vec<_>>();
+                if cells.len() != clue_count { return None; }
+                let mut puzzle = Sudoku::empty();
+                cells.into_iter().for_each(|cell| puzzle[cell] = grid[cell]);
+                FastBruteForceSolver::has_unique_solution(&puzzle).then_some(puzzle)
+            }).collect::<HashSet<_>>();
+
+            let base = GenerationBase::solution_grid(grid.clone(), clue_count, symmetry).unwrap();
+            let puzzles = first_sudokus(&base, usize::MAX);
+            assert_eq!(puzzles.len(), expected.len());
+            assert_eq!(puzzles.into_iter().collect::<HashSet<_>>(), expected);
+        }
+    }
+
+    /// The first few sudokus from one of the jobs of a base split into `count` jobs
+    fn first_sudokus_of_job(base: &GenerationBase, count: usize, job: usize, take: usize) -> Vec<Sudoku> {
+        let subtree = base.subtrees(count).swap_remove(job);
+        base.iter_subtree(subtree).unwrap().take(take).map(|(_, _, sudoku)| sudoku.borrow().sudoku().clone()).collect()
+    }
+
+    #[test]
+    fn random_puzzles_are_reproducible_and_minimal() {
+        let symmetry = DihedralSubgroup::CentralSymm;
+        let puzzles = first_sudokus(&GenerationBase::Random { seed: 42, symmetry }, 5);
+        assert_eq!(first_sudokus(&GenerationBase::Random { seed: 42, symmetry }, 5), puzzles);
+        assert_ne!(first_sudokus(&GenerationBase::Random { seed: 43, symmetry }, 5), puzzles);
+        for puzzle in &puzzles {
+            assert!(FastBruteForceSolver::has_unique_solution(puzzle));
+            for &orbit in symmetry.orbits() {
+                // The clues are a union of orbits, none of which can be removed
+                assert!(orbit.iter().all(|&cell| (puzzle[cell] == 0) == (puzzle[orbit[0]] == 0)));
+                if puzzle[orbit[0]] == 0 { continue; }
+                let mut removed = puzzle.clone();
+                orbit.iter().for_each(|&cell| removed[cell] = 0);
+                assert!(!FastBruteForceSolver::has_unique_solution(&removed));
+            }
+        }
+    }
+
+    #[test]
+    fn random_puzzles_depend_only_on_the_seed() {
+        let base = GenerationBase::Random { seed: 42, symmetry: DihedralSubgroup::Trivial };
+        assert_eq!(first_sudokus_of_job(&base, 1, 0, 3), first_sudokus(&base, 3));
+
+        // However many jobs a run is split into, each stream makes the same puzzles
+        let streams = RANDOM_STREAMS as usize;
+        let two_streams = first_sudokus_of_job(&base, streams / 2, 0, 3);
+        assert_eq!(first_sudokus_of_job(&base, streams, 0, 2), [two_streams[0].clone(), two_streams[2].clone()]);
+        assert_eq!(first_sudokus_of_job(&base, streams, 1, 1), [two_streams[1].clone()]);
+        assert_eq!(first_sudokus_of_job(&base, 2, 1, 1), first_sudokus_of_job(&base, 6, 3, 1));
+
+        // Splitting the streams between more jobs than there are leaves some without any
+        assert!(first_sudokus_of_job(&base, 2 * streams, 0, 1).is_empty());
+    }
+
+    #[test]
+    fn random_grids_depend_only_on_the_seed() {
+        let base = GenerationBase::RandomGrids { seed: 42 };
+        let grids = first_sudokus(&base, 5);
+        assert_eq!(first_sudokus(&GenerationBase::RandomGrids { seed: 42 }, 5), grids);
+        assert_ne!(first_sudokus(&GenerationBase::RandomGrids { seed: 43 }, 5), grids);
+        assert!(grids.iter().all(|grid| grid.digits().all(|&digit| digit != 0) && FastBruteForceSolver::has_unique_solution(grid)));
+        assert!(grids.iter().enumerate().all(|(idx, grid)| !grids[.. idx].contains(grid)));
+
+        // Jobs starting at the same stream make the same grids, however many jobs there are
+        assert_eq!(first_sudokus_of_job(&base, 1, 0, 5), grids);
+        assert_eq!(first_sudokus_of_job(&base, 3, 2, 1), first_sudokus_of_job(&base, 6, 4, 1));
+    }
+}
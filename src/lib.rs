@@ -0,0 +1,16 @@
+pub mod bitmask;
+pub mod checkpoint;
+pub mod dfs_with_progress;
+pub mod expansion;
+pub mod fast_solver;
+pub mod filter;
+pub mod generate;
+pub mod logic;
+pub mod minlex;
+pub mod pipeline;
+pub mod random;
+pub mod rating;
+pub mod recipe;
+pub mod sudoku;
+pub mod symmetry;
+pub mod template;
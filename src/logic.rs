@@ -1,15 +1,105 @@
 use crate::bitmask::Bitmask;
 use crate::pipeline::RegionMaskedSudoku;
 
+mod als;
+mod basics;
+mod chains;
+mod deduction;
+mod fish;
+mod uniqueness;
+mod wings;
+
+pub use deduction::{cell_name, cells_name, region_name, AlmostLockedSet, Chain, ChainNode, Deduction, Technique};
+
+use chains::ChainKind;
+use fish::FishKind;
+
+/// The maximum number of nodes in the chains used by `Tier::Chains`
+pub const DEFAULT_MAX_CHAIN_LENGTH: usize = 12;
+
+/// Tiers of techniques beyond the basics, from easiest to hardest. Solving with
+/// a tier means using its techniques along with those of all of the tiers below
+/// it, always preferring the easiest technique that makes progress.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Tier {
+    Basics,
+    Fish,
+    Wings,
+    Chains,
+    Als,
+}
+
+impl Tier {
+
+    /// The techniques of this tier and all the tiers below it, other than the basics,
+    /// in the order that they should be tried
+    pub fn techniques(&self) -> Vec<Technique> {
+        let mut techniques = self.previous().map(|tier| tier.techniques()).unwrap_or_default();
+        techniques.extend(match self {
+            Self::Basics => vec![],
+            Self::Fish => vec![
+                Technique::Fish(2), Technique::Fish(3), Technique::FinnedFish(2), Technique::SashimiFish(2),
+                Technique::Fish(4), Technique::FinnedFish(3), Technique::SashimiFish(3),
+                Technique::FinnedFish(4), Technique::SashimiFish(4),
+            ],
+            Self::Wings => vec![
+                Technique::Skyscraper, Technique::TwoStringKite, Technique::EmptyRectangle, Technique::WWing,
+                Technique::XyWing, Technique::XyzWing, Technique::SimpleColoring,
+            ],
+            Self::Chains => chain_techniques(DEFAULT_MAX_CHAIN_LENGTH),
+            Self::Als => vec![Technique::AlsXz, Technique::AlsXyWing, Technique::DeathBlossom],
+        });
+        techniques
+    }
+
+    /// The tier below this one, if there is one
+    pub fn previous(&self) -> Option<Self> {
+        match self {
+            Self::Basics => None,
+            Self::Fish => Some(Self::Basics),
+            Self::Wings => Some(Self::Fish),
+            Self::Chains => Some(Self::Wings),
+            Self::Als => Some(Self::Chains),
+        }
+    }
+}
+
+/// The uniqueness techniques, which are only used by solvers that have been told
+/// to assume that the puzzle has a unique solution with `assume_uniqueness`
+pub fn uniqueness_techniques() -> Vec<Technique> {
+    vec![
+        Technique::UniqueRectangle(1), Technique::AvoidableRectangle(1), Technique::UniqueRectangle(2), Technique::UniqueRectangle(4),
+        Technique::UniqueRectangle(5), Technique::UniqueRectangle(6), Technique::AvoidableRectangle(2), Technique::UniqueRectangle(3),
+        Technique::BugPlusOne,
+    ]
+}
+
+/// The chain techniques, searching for chains of at most the given number of nodes
+pub fn chain_techniques(max_length: usize) -> Vec<Technique> {
+    vec![Technique::XChain(max_length), Technique::XyChain(max_length), Technique::Aic(max_length)]
+}
+
 /// Solver capable of performing basic logic:
 /// - Naked and Hidden Singles
 /// - Pointing and Claiming
 /// - Naked and Hidden Subsets
+///
+/// along with more advanced techniques, one deduction at a time, using `step_with`:
+/// - X-Wings, Swordfish and Jellyfish, including finned and sashimi ones
+/// - XY-Wings, XYZ-Wings and W-Wings
+/// - Skyscrapers, 2-String Kites, Empty Rectangles and Simple Coloring
+/// - X-Chains, XY-Chains and alternating inference chains with group nodes
+/// - ALS-XZ, ALS-XY-Wings and Death Blossoms
+/// - Unique Rectangles, Avoidable Rectangles and BUG+1, but only once told to
+///   assume that the puzzle has a unique solution
 pub struct BasicSolver {
     candidate_positions: [Bitmask<u128>; 9],
     candidate_missing_regions: [Bitmask<u32>; 9],
     region_missing_candidates: [Bitmask<u16>; 27],
     unplaced: Bitmask<u128>,
+    givens: Bitmask<u128>,
+    uniqueness: bool,
+    log: Option<Vec<Deduction>>,
 }
 
 impl BasicSolver {
@@ -21,10 +111,14 @@ impl BasicSolver {
             candidate_missing_regions: [ALL_REGIONS; 9],
             region_missing_candidates: [ALL_VALUES; 27],
             unplaced: ALL_CELLS,
+            givens: Bitmask::<u128>::empty(),
+            uniqueness: false,
+            log: None,
         };
         for (cell, &val) in sudoku.sudoku().digits().enumerate() {
             if val != 0 {
                 result.place_hidden_single(Bitmask::<u128>::singleton(cell), val as usize - 1);
+                result.givens.set(cell);
             }
         }
         result
@@ -37,6 +131,21 @@ impl BasicSolver {
         }
     }
 
+    /// Allow the uniqueness techniques, which are only valid if the puzzle has a unique solution
+    pub fn assume_uniqueness(&mut self) {
+        self.uniqueness = true;
+    }
+
+    /// Start recording every deduction that the solver makes from now on
+    pub fn enable_log(&mut self) {
+        self.log.get_or_insert_with(Vec::new);
+    }
+
+    /// Take the deductions recorded so far, in the order they were made
+    pub fn take_log(&mut self) -> Vec<Deduction> {
+        self.log.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
     /// Carry out all basic deductions of the simplest kind for
     /// which any deductions exist
     pub fn step_basics(&mut self) -> Option<bool> {
@@ -50,6 +159,65 @@ impl BasicSolver {
         while let Some(true) = self.step_basics() {}
     }
 
+    /// Carry out basic deductions as `step_basics` does, or if there are none, a single
+    /// deduction using the first of the given techniques that makes any progress
+    pub fn step_with(&mut self, techniques: &[Technique]) -> Option<bool> {
+        if self.step_basics()? { return Some(true); }
+        for &technique in techniques {
+            if let Some(deduction) = self.find(technique) {
+                self.apply(deduction);
+                return Some(true);
+            }
+        }
+        Some(false)
+    }
+
+    /// Carry out deductions with `step_with` until no more remain
+    pub fn solve_with(&mut self, techniques: &[Technique]) {
+        while let Some(true) = self.step_with(techniques) {}
+    }
+
+    /// Find a single deduction using the given technique, without applying it
+    pub fn find(&self, technique: Technique) -> Option<Deduction> {
+        match technique {
+            Technique::NakedSingle => self.find_naked_single(),
+            Technique::HiddenSingle => self.find_hidden_single(),
+            Technique::Pointing | Technique::Claiming => self.find_intersection(technique),
+            Technique::NakedSubset(size) => self.find_naked_subset(size),
+            Technique::HiddenSubset(size) => self.find_hidden_subset(size),
+            Technique::Fish(size) => self.find_fish(size, FishKind::Basic),
+            Technique::FinnedFish(size) => self.find_fish(size, FishKind::Finned),
+            Technique::SashimiFish(size) => self.find_fish(size, FishKind::Sashimi),
+            Technique::XyWing => self.find_xy_wing(),
+            Technique::XyzWing => self.find_xyz_wing(),
+            Technique::WWing => self.find_w_wing(),
+            Technique::Skyscraper => self.find_skyscraper(),
+            Technique::TwoStringKite => self.find_two_string_kite(),
+            Technique::EmptyRectangle => self.find_empty_rectangle(),
+            Technique::SimpleColoring => self.find_simple_coloring(),
+            Technique::XChain(max_length) => self.find_chain(ChainKind::X, max_length),
+            Technique::XyChain(max_length) => self.find_chain(ChainKind::Xy, max_length),
+            Technique::Aic(max_length) => self.find_chain(ChainKind::Aic, max_length),
+            Technique::AlsXz => self.find_als_xz(),
+            Technique::AlsXyWing => self.find_als_xy_wing(),
+            Technique::DeathBlossom => self.find_death_blossom(),
+            Technique::UniqueRectangle(kind) => self.find_unique_rectangle(kind),
+            Technique::AvoidableRectangle(kind) => self.find_avoidable_rectangle(kind),
+            Technique::BugPlusOne => self.find_bug_plus_one(),
+        }
+    }
+
+    /// Apply the placements and eliminations of a deduction, recording it in the log
+    pub fn apply(&mut self, deduction: Deduction) {
+        for &(cell, digit) in &deduction.eliminations {
+            self.candidate_positions[digit as usize - 1].unset(cell);
+        }
+        for &(cell, digit) in &deduction.placements {
+            self.place_hidden_single(Bitmask::<u128>::singleton(cell), digit as usize - 1);
+        }
+        record(&mut self.log, || deduction);
+    }
+
     /// Check if the puzzle is solved
     pub fn is_solved(&self) -> bool {
         self.unplaced.is_empty()
@@ -94,6 +262,7 @@ impl BasicSolver {
             for val in 0 .. 9 {
                 if self.candidate_positions[val].contains(cell) {
                     self.place_naked_single(cell, val);
+                    record(&mut self.log, || placement(Technique::NakedSingle, None, cell, val));
                     break;
                 }
             }
@@ -109,8 +278,10 @@ impl BasicSolver {
                 let positions = REGIONS[region] & self.candidate_positions[val];
                 match positions.count_ones() {
                     0 => return None,
-                    1 => {
+                    // The single may have just been placed from another of its regions
+                    1 if (positions & self.unplaced).is_not_empty() => {
                         self.place_hidden_single(positions, val);
+                        record(&mut self.log, || placement(Technique::HiddenSingle, Some(region), positions.as_bit_iter().next().unwrap(), val));
                         made_progress = true;
                     },
                     _ => {},
@@ -124,13 +295,16 @@ impl BasicSolver {
     fn do_intersections(&mut self) -> bool {
         let mut made_progress = false;
         for (val, positions) in self.candidate_positions.iter_mut().enumerate() {
-            for (triad, other_line, other_box) in TRIADS {
+            for (idx, &(triad, other_line, other_box)) in TRIADS.iter().enumerate() {
                 if (*positions & triad).is_not_empty() {
-                    match ((*positions & other_line).is_empty(), (*positions & other_box).is_empty()) {
-                        (false, false) | (true, true) => {},
-                        (true, false) => { *positions &= !other_box; made_progress = true; },
-                        (false, true) => { *positions &= !other_line; made_progress = true; },
-                    }
+                    let (technique, eliminated) = match ((*positions & other_line).is_empty(), (*positions & other_box).is_empty()) {
+                        (false, false) | (true, true) => continue,
+                        (true, false) => (Technique::Claiming, *positions & other_box),
+                        (false, true) => (Technique::Pointing, *positions & other_line),
+                    };
+                    record(&mut self.log, || intersection(technique, idx, *positions & triad, eliminated, val));
+                    *positions &= !eliminated;
+                    made_progress = true;
                 }
             }
         }
@@ -138,6 +312,7 @@ impl BasicSolver {
     }
 
     /// Find and apply all Naked and Hidden Subsets
+    #[allow(clippy::needless_range_loop)]
     fn do_subsets(&mut self) -> bool {
         let mut made_progress = false;
         for region in 0 .. 27 {
@@ -148,17 +323,115 @@ impl BasicSolver {
                 if sz < 2 || sz + 2 > missing_count { continue; }
                 let positions = values.as_bit_iter().map(|val| self.candidate_positions[val]).reduce(|a, b| a | b).unwrap() & REGIONS[region];
                 if positions.count_ones() == sz {
+                    let mut eliminations = Vec::new();
                     for other_val in (ALL_VALUES & !values).as_bit_iter() {
-                        if (self.candidate_positions[other_val] & positions).is_not_empty() {
+                        let eliminated = self.candidate_positions[other_val] & positions;
+                        if eliminated.is_not_empty() {
+                            if self.log.is_some() { eliminations.extend(eliminated.as_bit_iter().map(|cell| (cell, other_val as u8 + 1))); }
                             self.candidate_positions[other_val] &= !positions;
                             made_progress = true;
                         }
                     }
+                    if !eliminations.is_empty() {
+                        // Every hidden subset is also a naked subset of the other cells and
+                        // digits of the region, so describe it by whichever is smaller
+                        let unplaced = REGIONS[region] & self.unplaced;
+                        let (technique, cells, digits) = if missing_count - sz <= sz {
+                            (Technique::NakedSubset((missing_count - sz) as usize), unplaced & !positions, self.region_missing_candidates[region] & !values)
+                        } else {
+                            (Technique::HiddenSubset(sz as usize), positions, values)
+                        };
+                        eliminations.sort();
+                        record(&mut self.log, || Deduction {
+                            technique,
+                            regions: vec![region],
+                            cells: cells.as_bit_iter().collect(),
+                            digits: digits.as_bit_iter().map(|val| val as u8 + 1).collect(),
+                            placements: Vec::new(),
+                            eliminations,
+                            chain: None,
+                            sets: Vec::new(),
+                        });
+                    }
                 }
             }
         }
         made_progress
     }
+
+    /// The unplaced cells which still have the given value as a candidate
+    fn open_positions(&self, val: usize) -> Bitmask<u128> {
+        self.candidate_positions[val] & self.unplaced
+    }
+
+    /// The values which are still candidates in the given cell
+    fn cell_candidates(&self, cell: usize) -> Bitmask<u16> {
+        Bitmask::<u16>::from_iter((0 .. 9).filter(|&val| self.candidate_positions[val].contains(cell)))
+    }
+
+    /// The unplaced cells which have exactly two candidates
+    fn bivalue_cells(&self) -> Vec<usize> {
+        self.unplaced.as_bit_iter().filter(|&cell| self.cell_candidates(cell).count_ones() == 2).collect()
+    }
+
+    /// Every region in which the given value is a candidate in exactly two unplaced
+    /// cells, as the region and the two cells. A pair of cells in both a line and a
+    /// box appears once for each.
+    fn conjugate_pairs(&self, val: usize) -> Vec<(usize, usize, usize)> {
+        let positions = self.open_positions(val);
+        (0 .. 27).filter_map(|region| {
+            let cells = REGIONS[region] & positions;
+            if cells.count_ones() != 2 { return None; }
+            let mut cells = cells.as_bit_iter();
+            Some((region, cells.next().unwrap(), cells.next().unwrap()))
+        }).collect()
+    }
+}
+
+/// Eliminations of a value from each of the given cells
+fn eliminations_of(cells: Bitmask<u128>, val: usize) -> Vec<(usize, u8)> {
+    cells.as_bit_iter().map(|cell| (cell, val as u8 + 1)).collect()
+}
+
+/// Add a deduction to the log, if there is one
+fn record<F: FnOnce() -> Deduction>(log: &mut Option<Vec<Deduction>>, deduction: F) {
+    if let Some(log) = log { log.push(deduction()); }
+}
+
+/// A deduction which places a single digit
+fn placement(technique: Technique, region: Option<usize>, cell: usize, val: usize) -> Deduction {
+    Deduction {
+        technique,
+        regions: region.into_iter().collect(),
+        cells: vec![cell],
+        digits: vec![val as u8 + 1],
+        placements: vec![(cell, val as u8 + 1)],
+        eliminations: Vec::new(),
+        chain: None,
+        sets: Vec::new(),
+    }
+}
+
+/// A Pointing or Claiming deduction from the candidates for a value in a triad
+fn intersection(technique: Technique, triad: usize, cells: Bitmask<u128>, eliminated: Bitmask<u128>, val: usize) -> Deduction {
+    let (line, block) = triad_regions(triad);
+    Deduction {
+        technique,
+        regions: if technique == Technique::Pointing { vec![block, line] } else { vec![line, block] },
+        cells: cells.as_bit_iter().collect(),
+        digits: vec![val as u8 + 1],
+        placements: Vec::new(),
+        eliminations: eliminations_of(eliminated, val),
+        chain: None,
+        sets: Vec::new(),
+    }
+}
+
+/// The line and box containing a triad
+fn triad_regions(triad: usize) -> (usize, usize) {
+    let cell = TRIADS[triad].0.as_bit_iter().next().unwrap();
+    let regions = CELL_REGIONS[cell].as_bit_iter().collect::<Vec<_>>();
+    (if triad < 27 { regions[0] } else { regions[1] }, regions[2])
 }
 
 pub const ALL_VALUES: Bitmask<u16> = Bitmask::<u16>::from(0b111111111);
@@ -421,3 +694,90 @@ pub const PEERS: [Bitmask::<u128>; 81] = [
     Bitmask::<u128>::from(0b101111111_111000000_111000000_010000000_010000000_010000000_010000000_010000000_010000000),
     Bitmask::<u128>::from(0b011111111_111000000_111000000_100000000_100000000_100000000_100000000_100000000_100000000),
 ];
+
+#[cfg(test)]
+mod tests {
+    use itertools::iproduct;
+
+    use super::*;
+    use crate::sudoku::Sudoku;
+
+    /// A solver for the given puzzle, with some candidates eliminated. Each elimination
+    /// is written like `r15c289<>16`, which eliminates 1 and 6 from every cell in
+    /// rows 1 and 5 and columns 2, 8 and 9. A digit can also be placed in a cell as
+    /// if it had been solved rather than given, written like `r1c1=5`.
+    pub(super) fn solver_with(puzzle: &str, eliminations: &[&str]) -> BasicSolver {
+        let mut solver = BasicSolver::for_region_masked_sudoku(&RegionMaskedSudoku::from_sudoku(&Sudoku::from_str(puzzle).unwrap()));
+        let number = |c: char| c.to_digit(10).unwrap() as usize;
+        for elimination in eliminations {
+            if let Some((cell, digit)) = elimination.split_once('=') {
+                let (row, col) = cell.strip_prefix('r').and_then(|cell| cell.split_once('c')).unwrap();
+                let cell = 9 * (row.parse::<usize>().unwrap() - 1) + col.parse::<usize>().unwrap() - 1;
+                solver.place_hidden_single(Bitmask::<u128>::singleton(cell), digit.parse::<usize>().unwrap() - 1);
+                continue;
+            }
+            let (cells, digits) = elimination.split_once("<>").unwrap();
+            let (rows, cols) = cells.strip_prefix('r').and_then(|cells| cells.split_once('c')).unwrap();
+            for (row, col, digit) in iproduct!(rows.chars(), cols.chars(), digits.chars()) {
+                solver.candidate_positions[number(digit) - 1].unset(9 * (number(row) - 1) + number(col) - 1);
+            }
+        }
+        solver
+    }
+
+    /// A solver for an empty grid, with some candidates eliminated as in `solver_with`
+    pub(super) fn empty_grid_with(eliminations: &[&str]) -> BasicSolver {
+        solver_with(&".".repeat(81), eliminations)
+    }
+
+    /// The deduction found with the given technique, if any, as it is displayed
+    pub(super) fn found(solver: &BasicSolver, technique: Technique) -> Option<String> {
+        solver.find(technique).map(|deduction| deduction.to_string())
+    }
+
+    fn log_of_step(solver: &mut BasicSolver) -> Vec<String> {
+        solver.enable_log();
+        assert_eq!(solver.step_basics(), Some(true));
+        solver.take_log().iter().map(|deduction| deduction.to_string()).collect()
+    }
+
+    #[test]
+    fn solve_path_of_a_short_puzzle() {
+        let mut solver = solver_with("410309025032150940958724316825437169091500432340912050289643571573291684164875293", &[]);
+        // Every one of these placements is the only place for its digit in its row,
+        // column and box at once, but it's only logged for the first of them
+        assert_eq!(log_of_step(&mut solver), [
+            "Hidden Single 6 in r6 (r6c3): r6c3=6",
+            "Hidden Single 6 in c1 (r2c1): r2c1=6",
+            "Hidden Single 6 in c6 (r5c6): r5c6=6",
+            "Hidden Single 6 in b2 (r1c5): r1c5=6",
+            "Hidden Single 7 in r2 (r2c9): r2c9=7",
+            "Hidden Single 7 in r5 (r5c1): r5c1=7",
+            "Hidden Single 7 in r6 (r6c7): r6c7=7",
+            "Hidden Single 7 in c3 (r1c3): r1c3=7",
+            "Hidden Single 8 in r1 (r1c7): r1c7=8",
+            "Hidden Single 8 in r2 (r2c6): r2c6=8",
+            "Hidden Single 8 in r5 (r5c5): r5c5=8",
+            "Hidden Single 8 in r6 (r6c9): r6c9=8",
+        ]);
+        assert!(solver.is_solved());
+        assert_eq!(solver.step_basics(), Some(false));
+        assert!(solver.take_log().is_empty());
+    }
+
+    #[test]
+    fn hidden_subsets_are_logged_as_smaller_naked_subsets() {
+        // Row 1 is missing 6789, and 6 and 7 are only in r1c6 and r1c9: a Hidden Pair,
+        // which is written as the Naked Pair 89 in the other two cells. That pair is
+        // also in box 3, where it's the complement of a Hidden Septuple.
+        let mut solver = solver_with(&format!("12345{}", ".".repeat(76)), &["r1c78<>67"]);
+        assert_eq!(log_of_step(&mut solver), [
+            "Naked Pair 89 in r1 (r1c7,r1c8): r1c6<>8, r1c6<>9, r1c9<>8, r1c9<>9",
+            "Naked Pair 89 in b3 (r1c7,r1c8): r2c7<>8, r2c7<>9, r2c8<>8, r2c8<>9, r2c9<>8, r2c9<>9, r3c7<>8, r3c7<>9, r3c8<>8, r3c8<>9, r3c9<>8, r3c9<>9",
+        ]);
+
+        // With more digits missing from the row, the Hidden Pair is the smaller of the two
+        let mut solver = solver_with(&format!("123{}", ".".repeat(78)), &["r1c5678<>45"]);
+        assert_eq!(log_of_step(&mut solver), ["Hidden Pair 45 in r1 (r1c4,r1c9): r1c4<>6, r1c4<>7, r1c4<>8, r1c4<>9, r1c9<>6, r1c9<>7, r1c9<>8, r1c9<>9"]);
+    }
+}
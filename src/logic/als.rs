@@ -0,0 +1,194 @@
+use itertools::Itertools;
+
+use crate::bitmask::Bitmask;
+
+use super::{eliminations_of, AlmostLockedSet, BasicSolver, Deduction, Technique, ALL_CELLS, PEERS, REGIONS};
+
+/// An Almost Locked Set: N unplaced cells in a single region with N+1 candidates
+/// between them, so that removing any one candidate leaves a locked set
+struct Als {
+    region: usize,
+    cells: Bitmask<u128>,
+    values: Bitmask<u16>,
+    /// The cells of the set which have each value as a candidate
+    positions: [Bitmask<u128>; 9],
+    /// The cells which see every cell of the set having each value as a candidate
+    seen_by_all: [Bitmask<u128>; 9],
+}
+
+impl Als {
+
+    /// The values which are restricted commons of two disjoint sets: every cell in either
+    /// set with the value sees every such cell in the other, so at most one set holds it
+    fn restricted_commons(&self, other: &Als) -> Bitmask<u16> {
+        if (self.cells & other.cells).is_not_empty() { return Bitmask::<u16>::empty(); }
+        Bitmask::<u16>::from_iter((self.values & other.values).as_bit_iter()
+            .filter(|&val| (other.positions[val] & !self.seen_by_all[val]).is_empty()))
+    }
+
+    fn describe(&self) -> AlmostLockedSet {
+        AlmostLockedSet {
+            region: self.region,
+            cells: self.cells.as_bit_iter().collect(),
+            digits: self.values.as_bit_iter().map(|val| val as u8 + 1).collect(),
+        }
+    }
+}
+
+impl BasicSolver {
+
+    /// Find an ALS-XZ: two Almost Locked Sets with a restricted common X. One of them
+    /// must be locked, so any other common digit Z is eliminated from every cell
+    /// seeing all of the cells of both sets which have Z as a candidate.
+    pub(super) fn find_als_xz(&self) -> Option<Deduction> {
+        let sets = self.almost_locked_sets();
+        for (a, b) in sets.iter().tuple_combinations() {
+            for x in a.restricted_commons(b).as_bit_iter() {
+                for z in (a.values & b.values).as_bit_iter().filter(|&z| z != x) {
+                    let eliminated = self.open_positions(z) & a.seen_by_all[z] & b.seen_by_all[z];
+                    if eliminated.is_empty() { continue; }
+                    return Some(als_deduction(Technique::AlsXz, &[a, b], None, Bitmask::<u16>::singleton(x), eliminated, z));
+                }
+            }
+        }
+        None
+    }
+
+    /// Find an ALS-XY-Wing: a pivot Almost Locked Set with a restricted common X with
+    /// one set and a different restricted common Y with another. One of those two sets
+    /// must be locked, so any digit Z common to both is eliminated from every cell
+    /// seeing all of the cells of both sets which have Z as a candidate.
+    pub(super) fn find_als_xy_wing(&self) -> Option<Deduction> {
+        let sets = self.almost_locked_sets();
+        for pivot in &sets {
+            let linked = sets.iter()
+                .map(|set| (set, pivot.restricted_commons(set)))
+                .filter(|(_, commons)| commons.is_not_empty())
+                .collect::<Vec<_>>();
+            for (&(a, a_commons), &(b, b_commons)) in linked.iter().tuple_combinations() {
+                if (a.cells & b.cells).is_not_empty() { continue; }
+                // The restricted commons must differ, or both sets could be locked at once
+                let pairs = a_commons.as_bit_iter().flat_map(|x| b_commons.as_bit_iter().map(move |y| (x, y))).filter(|(x, y)| x != y);
+                for (x, y) in pairs {
+                    for z in (a.values & b.values).as_bit_iter().filter(|&z| z != x && z != y) {
+                        let eliminated = self.open_positions(z) & a.seen_by_all[z] & b.seen_by_all[z];
+                        if eliminated.is_empty() { continue; }
+                        let commons = Bitmask::<u16>::from_iter([x, y].into_iter());
+                        return Some(als_deduction(Technique::AlsXyWing, &[a, pivot, b], None, commons, eliminated, z));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Find a Death Blossom: a stem cell with, for each of its candidates, a petal
+    /// Almost Locked Set whose cells with that candidate all see the stem. Whichever
+    /// candidate the stem holds locks its petal, so a digit Z common to every petal
+    /// is eliminated from every cell seeing all of the petals' cells with Z.
+    pub(super) fn find_death_blossom(&self) -> Option<Deduction> {
+        let sets = self.almost_locked_sets();
+        for stem in self.unplaced.as_bit_iter() {
+            let stem_values = self.cell_candidates(stem);
+            if stem_values.count_ones() < 2 { continue; }
+            for z in (0 .. 9).filter(|&z| !stem_values.contains(z)) {
+                let petals = stem_values.as_bit_iter().map(|val| {
+                    sets.iter().filter(|set| {
+                        !set.cells.contains(stem) && set.values.contains(val) && set.values.contains(z)
+                            && (set.positions[val] & !PEERS[stem]).is_empty()
+                    }).collect::<Vec<_>>()
+                }).collect::<Vec<_>>();
+                let mut chosen = Vec::new();
+                if let Some(eliminated) = self.choose_petals(&petals, z, self.open_positions(z), &mut chosen) {
+                    return Some(als_deduction(Technique::DeathBlossom, &chosen, Some(stem), stem_values, eliminated, z));
+                }
+            }
+        }
+        None
+    }
+
+    /// Choose a petal for each of the stem's candidates in turn, such that some
+    /// cells still see all of the chosen petals' cells with the digit Z
+    fn choose_petals<'a>(&self, petals: &[Vec<&'a Als>], z: usize, targets: Bitmask<u128>, chosen: &mut Vec<&'a Als>) -> Option<Bitmask<u128>> {
+        let Some((options, rest)) = petals.split_first() else { return Some(targets); };
+        for &petal in options {
+            let remaining = targets & petal.seen_by_all[z];
+            if remaining.is_empty() { continue; }
+            chosen.push(petal);
+            if let Some(eliminated) = self.choose_petals(rest, z, remaining, chosen) {
+                return Some(eliminated);
+            }
+            chosen.pop();
+        }
+        None
+    }
+
+    /// Every Almost Locked Set, each listed once even if it lies in both a box and a line
+    #[allow(clippy::needless_range_loop)]
+    fn almost_locked_sets(&self) -> Vec<Als> {
+        let mut sets = Vec::new();
+        for region in 0 .. 27 {
+            // An ALS of N cells needs at least N+1 digits missing from the region
+            let missing_count = self.region_missing_candidates[region].count_ones();
+            if missing_count < 2 { continue; }
+            for cells in (REGIONS[region] & self.unplaced).as_subset_iter() {
+                let size = cells.count_ones();
+                if size == 0 || size >= missing_count { continue; }
+                if (0 .. region).any(|other| (cells & !REGIONS[other]).is_empty()) { continue; }
+                let values = cells.as_bit_iter().map(|cell| self.cell_candidates(cell)).fold(Bitmask::<u16>::empty(), |a, b| a | b);
+                if values.count_ones() != size + 1 { continue; }
+
+                let positions: [Bitmask<u128>; 9] = std::array::from_fn(|val| cells & self.candidate_positions[val]);
+                let seen_by_all = std::array::from_fn(|val| {
+                    if positions[val].is_empty() { return Bitmask::<u128>::empty(); }
+                    positions[val].as_bit_iter().fold(ALL_CELLS, |seen, cell| seen & PEERS[cell])
+                });
+                sets.push(Als { region, cells, values, positions, seen_by_all });
+            }
+        }
+        sets
+    }
+}
+
+/// A deduction from some Almost Locked Sets and their restricted common digits, along with
+/// the stem cell of a Death Blossom, eliminating a single digit from the given cells
+fn als_deduction(technique: Technique, sets: &[&Als], stem: Option<usize>, commons: Bitmask<u16>, eliminated: Bitmask<u128>, val: usize) -> Deduction {
+    Deduction {
+        technique,
+        regions: sets.iter().map(|set| set.region).collect(),
+        cells: sets.iter().flat_map(|set| set.cells.as_bit_iter()).chain(stem).sorted().dedup().collect(),
+        digits: commons.as_bit_iter().map(|val| val as u8 + 1).collect(),
+        placements: Vec::new(),
+        eliminations: eliminations_of(eliminated, val),
+        chain: None,
+        sets: sets.iter().map(|set| set.describe()).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{empty_grid_with, found};
+    use super::*;
+
+    #[test]
+    fn als_xz() {
+        // r1c1 is 12, and r5c1 13 and r5c7 23 are a set in row 5, with the restricted common 1
+        let solver = empty_grid_with(&["r1c1<>3456789", "r5c1<>2456789", "r5c7<>1456789"]);
+        assert_eq!(found(&solver, Technique::AlsXz).as_deref(), Some("ALS-XZ 1 in r1,r5 (r1c1{12}, r5c1,r5c7{123}): r1c7<>2"));
+    }
+
+    #[test]
+    fn als_xy_wing() {
+        // The pivot r4c1 23 and r4c3 24 has the restricted common 3 with r2c1 23, and both 2
+        // and 4 with r5c2 24. Only taking 3 and 4 leaves a digit, 2, common to the other two sets.
+        let solver = empty_grid_with(&["r24c1<>1456789", "r4c3<>1356789", "r5c2<>1356789"]);
+        assert_eq!(found(&solver, Technique::AlsXyWing).as_deref(), Some("ALS-XY-Wing 34 in r2,r4,r5 (r2c1{23}, r4c1,r4c3{234}, r5c2{24}): r1c2<>2, r2c2<>2, r3c2<>2, r4c1<>2, r5c1<>2, r6c1<>2"));
+    }
+
+    #[test]
+    fn death_blossom() {
+        // The stem r1c1 12 has the petals r1c5 13 for 1, and r2c2 24 and r2c8 34 for 2
+        let solver = empty_grid_with(&["r1c1<>3456789", "r1c5<>2456789", "r2c2<>1356789", "r2c8<>1256789"]);
+        assert_eq!(found(&solver, Technique::DeathBlossom).as_deref(), Some("Death Blossom 12 in r1,r2 r1c1 (r1c5{13}, r2c2,r2c8{234}): r1c7<>3, r1c8<>3, r1c9<>3, r2c4<>3, r2c5<>3, r2c6<>3"));
+    }
+}
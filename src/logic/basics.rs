@@ -0,0 +1,96 @@
+use itertools::Itertools;
+
+use crate::bitmask::Bitmask;
+
+use super::{intersection, placement, BasicSolver, Deduction, Technique, REGIONS, TRIADS};
+
+// Single deductions using the basic techniques, for when they need to be applied
+// one at a time rather than in bulk as `step_basics` does.
+impl BasicSolver {
+
+    pub(super) fn find_naked_single(&self) -> Option<Deduction> {
+        let cell = self.unplaced.as_bit_iter().find(|&cell| self.cell_candidates(cell).count_ones() == 1)?;
+        let val = self.cell_candidates(cell).as_bit_iter().next().unwrap();
+        Some(placement(Technique::NakedSingle, None, cell, val))
+    }
+
+    /// Find a Hidden Single, looking in the boxes before the lines
+    pub(super) fn find_hidden_single(&self) -> Option<Deduction> {
+        for region in (18 .. 27).chain(0 .. 18) {
+            for val in self.region_missing_candidates[region].as_bit_iter() {
+                let positions = REGIONS[region] & self.candidate_positions[val];
+                if positions.count_ones() == 1 {
+                    return Some(placement(Technique::HiddenSingle, Some(region), positions.as_bit_iter().next().unwrap(), val));
+                }
+            }
+        }
+        None
+    }
+
+    /// Find a Pointing or Claiming deduction, as given
+    pub(super) fn find_intersection(&self, technique: Technique) -> Option<Deduction> {
+        for (val, &positions) in self.candidate_positions.iter().enumerate() {
+            for (idx, &(triad, other_line, other_box)) in TRIADS.iter().enumerate() {
+                if (positions & triad).is_empty() { continue; }
+                let eliminated = match (technique, (positions & other_line).is_empty(), (positions & other_box).is_empty()) {
+                    (Technique::Claiming, true, false) => positions & other_box,
+                    (Technique::Pointing, false, true) => positions & other_line,
+                    _ => continue,
+                };
+                return Some(intersection(technique, idx, positions & triad, eliminated, val));
+            }
+        }
+        None
+    }
+
+    /// Find a Naked Subset: some cells of a region with only as many candidates between them
+    #[allow(clippy::needless_range_loop)]
+    pub(super) fn find_naked_subset(&self, size: usize) -> Option<Deduction> {
+        for region in 0 .. 27 {
+            let unplaced = REGIONS[region] & self.unplaced;
+            for cells in unplaced.as_subset_iter().filter(|cells| cells.count_ones() as usize == size) {
+                let values = cells.as_bit_iter().fold(Bitmask::<u16>::empty(), |values, cell| values | self.cell_candidates(cell));
+                if values.count_ones() as usize != size { continue; }
+                let eliminations = values.as_bit_iter()
+                    .flat_map(|val| (self.candidate_positions[val] & unplaced & !cells).as_bit_iter().map(move |cell| (cell, val as u8 + 1)))
+                    .sorted()
+                    .collect::<Vec<_>>();
+                if eliminations.is_empty() { continue; }
+                return Some(subset(Technique::NakedSubset(size), region, cells, values, eliminations));
+            }
+        }
+        None
+    }
+
+    /// Find a Hidden Subset: some digits which only appear in as many cells of a region
+    #[allow(clippy::needless_range_loop)]
+    pub(super) fn find_hidden_subset(&self, size: usize) -> Option<Deduction> {
+        for region in 0 .. 27 {
+            let missing = self.region_missing_candidates[region];
+            for values in missing.as_subset_iter().filter(|values| values.count_ones() as usize == size) {
+                let cells = values.as_bit_iter().fold(Bitmask::<u128>::empty(), |cells, val| cells | self.candidate_positions[val]) & REGIONS[region];
+                if cells.count_ones() as usize != size { continue; }
+                let eliminations = (missing & !values).as_bit_iter()
+                    .flat_map(|val| (self.candidate_positions[val] & cells).as_bit_iter().map(move |cell| (cell, val as u8 + 1)))
+                    .sorted()
+                    .collect::<Vec<_>>();
+                if eliminations.is_empty() { continue; }
+                return Some(subset(Technique::HiddenSubset(size), region, cells, values, eliminations));
+            }
+        }
+        None
+    }
+}
+
+fn subset(technique: Technique, region: usize, cells: Bitmask<u128>, values: Bitmask<u16>, eliminations: Vec<(usize, u8)>) -> Deduction {
+    Deduction {
+        technique,
+        regions: vec![region],
+        cells: cells.as_bit_iter().collect(),
+        digits: values.as_bit_iter().map(|val| val as u8 + 1).collect(),
+        placements: Vec::new(),
+        eliminations,
+        chain: None,
+        sets: Vec::new(),
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::VecDeque;
+
+use itertools::Itertools;
+
+use crate::bitmask::Bitmask;
+
+use super::{BasicSolver, Chain, ChainNode, Deduction, Technique, CELL_REGIONS, PEERS, REGIONS, TRIADS};
+
+/// The kinds of chains: X-Chains use a single digit, XY-Chains alternate between
+/// strong links within bivalue cells and weak links between cells, and general
+/// AICs may use any links, including those between groups of cells.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(super) enum ChainKind {
+    X,
+    Xy,
+    Aic,
+}
+
+/// A digit in one or more cells, all of which are within a single box and line
+struct Node {
+    val: usize,
+    cells: Bitmask<u128>,
+    regions: Bitmask<u32>,
+}
+
+/// The nodes of a chain that was found, given as indices into the graph, and the
+/// eliminations that follow from it
+type FoundChain = (Vec<usize>, Vec<(usize, u8)>);
+
+/// The nodes of a chain search, with the strong and weak links between them
+struct Graph {
+    nodes: Vec<Node>,
+    strong: Vec<Vec<usize>>,
+    weak: Vec<Vec<usize>>,
+}
+
+impl BasicSolver {
+
+    /// Find the shortest chain of the given kind, with at most `max_length` nodes,
+    /// which leads to any eliminations
+    pub(super) fn find_chain(&self, kind: ChainKind, max_length: usize) -> Option<Deduction> {
+        let graph = self.chain_graph(kind);
+        let mut best: Option<FoundChain> = None;
+        for start in 0 .. graph.nodes.len() {
+            let limit = best.as_ref().map_or(max_length, |(path, _)| path.len() - 1);
+            if let Some(found) = self.shortest_chain_from(&graph, kind, start, limit) {
+                best = Some(found);
+            }
+        }
+
+        let (path, eliminations) = best?;
+        let nodes = path.iter().map(|&idx| &graph.nodes[idx]).collect::<Vec<_>>();
+        let technique = match kind {
+            ChainKind::X => Technique::XChain(path.len()),
+            ChainKind::Xy => Technique::XyChain(path.len()),
+            ChainKind::Aic => Technique::Aic(path.len()),
+        };
+        Some(Deduction {
+            technique,
+            regions: Vec::new(),
+            cells: nodes.iter().flat_map(|node| node.cells.as_bit_iter()).sorted().dedup().collect(),
+            digits: nodes.iter().map(|node| node.val as u8 + 1).sorted().dedup().collect(),
+            placements: Vec::new(),
+            eliminations,
+            chain: Some(Chain {
+                nodes: nodes.iter().map(|node| ChainNode { digit: node.val as u8 + 1, cells: node.cells.as_bit_iter().collect() }).collect(),
+            }),
+            sets: Vec::new(),
+        })
+    }
+
+    /// Build the nodes and links that chains of the given kind can use
+    fn chain_graph(&self, kind: ChainKind) -> Graph {
+        let mut nodes = Vec::new();
+        for val in 0 .. 9 {
+            for cell in self.open_positions(val).as_bit_iter() {
+                nodes.push(Node { val, cells: Bitmask::<u128>::singleton(cell), regions: CELL_REGIONS[cell] });
+            }
+            if kind == ChainKind::Aic {
+                for &(triad, _, _) in &TRIADS {
+                    let cells = triad & self.open_positions(val);
+                    if cells.count_ones() >= 2 {
+                        let regions = cells.as_bit_iter().map(|cell| CELL_REGIONS[cell]).reduce(|a, b| a & b).unwrap();
+                        nodes.push(Node { val, cells, regions });
+                    }
+                }
+            }
+        }
+
+        let mut strong = vec![Vec::new(); nodes.len()];
+        let mut weak = vec![Vec::new(); nodes.len()];
+        for (a, b) in (0 .. nodes.len()).tuple_combinations() {
+            let (node_a, node_b) = (&nodes[a], &nodes[b]);
+            let (is_strong, is_weak) = if node_a.val == node_b.val {
+                let disjoint = (node_a.cells & node_b.cells).is_empty();
+                let shared_regions = node_a.regions & node_b.regions;
+                // Strong if the two nodes hold every candidate for the digit in a region they share
+                let is_strong = disjoint && shared_regions.as_bit_iter()
+                    .any(|region| REGIONS[region] & self.open_positions(node_a.val) == node_a.cells | node_b.cells);
+                (is_strong && kind != ChainKind::Xy, disjoint && shared_regions.is_not_empty())
+            } else if node_a.cells == node_b.cells && node_a.cells.count_ones() == 1 {
+                if kind == ChainKind::X { continue; }
+                let cell = node_a.cells.as_bit_iter().next().unwrap();
+                (self.cell_candidates(cell).count_ones() == 2, kind == ChainKind::Aic)
+            } else {
+                continue;
+            };
+            if is_strong { strong[a].push(b); strong[b].push(a); }
+            if is_weak { weak[a].push(b); weak[b].push(a); }
+        }
+        Graph { nodes, strong, weak }
+    }
+
+    /// Search outwards from the given node for the shortest chain which starts
+    /// there and leads to any eliminations, returning its nodes and eliminations
+    fn shortest_chain_from(&self, graph: &Graph, kind: ChainKind, start: usize, max_length: usize) -> Option<FoundChain> {
+        // Nodes reached by a strong link are true if the start is false,
+        // and nodes reached by a weak link from those are then false
+        let mut parents = [vec![None; graph.nodes.len()], vec![None; graph.nodes.len()]];
+        let mut queue = VecDeque::from([(start, false, 1)]);
+        while let Some((node, is_true, length)) = queue.pop_front() {
+            if is_true {
+                let eliminations = self.chain_eliminations(kind, &graph.nodes[start], &graph.nodes[node]);
+                if !eliminations.is_empty() {
+                    let mut path = vec![node];
+                    let mut state = (node, true);
+                    while let Some(parent) = parents[state.1 as usize][state.0] {
+                        path.push(parent);
+                        state = (parent, !state.1);
+                    }
+                    path.reverse();
+                    return Some((path, eliminations));
+                }
+            }
+            if length >= max_length { continue; }
+            let links = if is_true { &graph.weak[node] } else { &graph.strong[node] };
+            for &next in links {
+                if next == start || parents[!is_true as usize][next].is_some() { continue; }
+                parents[!is_true as usize][next] = Some(node);
+                queue.push_back((next, !is_true, length + 1));
+            }
+        }
+        None
+    }
+
+    /// The eliminations that follow from either the first or last node of a chain being true
+    fn chain_eliminations(&self, kind: ChainKind, first: &Node, last: &Node) -> Vec<(usize, u8)> {
+        let mut eliminations = Vec::new();
+        let chain_cells = first.cells | last.cells;
+        if first.val == last.val {
+            // Every cell seeing all of the cells of both nodes
+            let seen = chain_cells.as_bit_iter().fold(self.open_positions(first.val), |seen, cell| seen & PEERS[cell]);
+            eliminations.extend(seen.as_bit_iter().map(|cell| (cell, first.val as u8 + 1)));
+        } else if kind == ChainKind::Aic {
+            if first.cells == last.cells && first.cells.count_ones() == 1 {
+                // Both ends are in the same cell, so it can hold nothing else
+                let cell = first.cells.as_bit_iter().next().unwrap();
+                let others = self.cell_candidates(cell) & !Bitmask::<u16>::from_iter([first.val, last.val].into_iter());
+                eliminations.extend(others.as_bit_iter().map(|val| (cell, val as u8 + 1)));
+            } else {
+                // A single cell at one end seeing every cell of the other end can't hold the other digit
+                for (node, other) in [(first, last), (last, first)] {
+                    if node.cells.count_ones() != 1 || (other.cells & node.cells).is_not_empty() { continue; }
+                    let cell = node.cells.as_bit_iter().next().unwrap();
+                    if self.candidate_positions[other.val].contains(cell) && (other.cells & !PEERS[cell]).is_empty() {
+                        eliminations.push((cell, other.val as u8 + 1));
+                    }
+                }
+            }
+        }
+        eliminations.sort();
+        eliminations
+    }
+}
@@ -0,0 +1,244 @@
+use std::fmt;
+
+use itertools::Itertools;
+
+/// The techniques that the logical solvers can use
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Technique {
+    NakedSingle,
+    HiddenSingle,
+    Pointing,
+    Claiming,
+    NakedSubset(usize),
+    HiddenSubset(usize),
+    Fish(usize),
+    FinnedFish(usize),
+    SashimiFish(usize),
+    XyWing,
+    XyzWing,
+    WWing,
+    Skyscraper,
+    TwoStringKite,
+    EmptyRectangle,
+    SimpleColoring,
+    /// Chains are given the maximum number of nodes to search for, and in a
+    /// deduction, the number of nodes in the chain that was found
+    XChain(usize),
+    XyChain(usize),
+    Aic(usize),
+    AlsXz,
+    AlsXyWing,
+    DeathBlossom,
+    /// The uniqueness techniques are given their type
+    UniqueRectangle(usize),
+    AvoidableRectangle(usize),
+    BugPlusOne,
+}
+
+impl Technique {
+
+    /// Whether a deduction made using the other technique is one that this technique
+    /// could have made, which for chains means one of at most this many nodes
+    pub fn includes(&self, other: Technique) -> bool {
+        match (*self, other) {
+            (Self::XChain(max_length), Self::XChain(length))
+                | (Self::XyChain(max_length), Self::XyChain(length))
+                | (Self::Aic(max_length), Self::Aic(length)) => length <= max_length,
+            _ => *self == other,
+        }
+    }
+}
+
+impl fmt::Display for Technique {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NakedSingle => write!(f, "Naked Single"),
+            Self::HiddenSingle => write!(f, "Hidden Single"),
+            Self::Pointing => write!(f, "Pointing"),
+            Self::Claiming => write!(f, "Claiming"),
+            Self::NakedSubset(size) => write!(f, "Naked {}", subset_name(*size)),
+            Self::HiddenSubset(size) => write!(f, "Hidden {}", subset_name(*size)),
+            Self::Fish(size) => write!(f, "{}", fish_name(*size)),
+            Self::FinnedFish(size) => write!(f, "Finned {}", fish_name(*size)),
+            Self::SashimiFish(size) => write!(f, "Sashimi {}", fish_name(*size)),
+            Self::XyWing => write!(f, "XY-Wing"),
+            Self::XyzWing => write!(f, "XYZ-Wing"),
+            Self::WWing => write!(f, "W-Wing"),
+            Self::Skyscraper => write!(f, "Skyscraper"),
+            Self::TwoStringKite => write!(f, "2-String Kite"),
+            Self::EmptyRectangle => write!(f, "Empty Rectangle"),
+            Self::SimpleColoring => write!(f, "Simple Coloring"),
+            Self::XChain(_) => write!(f, "X-Chain"),
+            Self::XyChain(_) => write!(f, "XY-Chain"),
+            Self::Aic(_) => write!(f, "AIC"),
+            Self::AlsXz => write!(f, "ALS-XZ"),
+            Self::AlsXyWing => write!(f, "ALS-XY-Wing"),
+            Self::DeathBlossom => write!(f, "Death Blossom"),
+            Self::UniqueRectangle(kind) => write!(f, "Unique Rectangle Type {}", kind),
+            Self::AvoidableRectangle(kind) => write!(f, "Avoidable Rectangle Type {}", kind),
+            Self::BugPlusOne => write!(f, "BUG+1"),
+        }
+    }
+}
+
+fn subset_name(size: usize) -> String {
+    match size {
+        2 => "Pair".to_string(),
+        3 => "Triple".to_string(),
+        4 => "Quad".to_string(),
+        _ => format!("Subset ({})", size),
+    }
+}
+
+fn fish_name(size: usize) -> String {
+    match size {
+        2 => "X-Wing".to_string(),
+        3 => "Swordfish".to_string(),
+        4 => "Jellyfish".to_string(),
+        _ => format!("Fish ({})", size),
+    }
+}
+
+/// A single deduction made by a solver: the technique used, the regions, cells
+/// and digits making up the pattern that was found, and the placements and
+/// eliminations that follow from it.
+///
+/// Regions are numbered as in `REGIONS`, cells are numbered from 0 to 80
+/// and digits from 1 to 9. Deductions from Almost Locked Sets list the sets
+/// used, and their digits are the restricted commons between the sets.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Deduction {
+    pub technique: Technique,
+    pub regions: Vec<usize>,
+    pub cells: Vec<usize>,
+    pub digits: Vec<u8>,
+    pub placements: Vec<(usize, u8)>,
+    pub eliminations: Vec<(usize, u8)>,
+    pub chain: Option<Chain>,
+    pub sets: Vec<AlmostLockedSet>,
+}
+
+/// An Almost Locked Set: N cells within a region which have N+1 digits between them
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct AlmostLockedSet {
+    pub region: usize,
+    pub cells: Vec<usize>,
+    pub digits: Vec<u8>,
+}
+
+/// An alternating inference chain, whose links are alternately strong and weak,
+/// starting and ending with a strong link. Either the first or the last node is true.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Chain {
+    pub nodes: Vec<ChainNode>,
+}
+
+/// A node of a chain: a digit in a single cell, or in a group of cells within a
+/// box and a line, which is true if the digit is in any of the cells
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ChainNode {
+    pub digit: u8,
+    pub cells: Vec<usize>,
+}
+
+/// Write a cell in the usual notation, e.g. `r4c5`
+pub fn cell_name(cell: usize) -> String {
+    format!("r{}c{}", cell / 9 + 1, cell % 9 + 1)
+}
+
+/// Write a region in the usual notation, e.g. `r4`, `c5` or `b6`
+pub fn region_name(region: usize) -> String {
+    match region / 9 {
+        0 => format!("r{}", region % 9 + 1),
+        1 => format!("c{}", region % 9 + 1),
+        _ => format!("b{}", region % 9 + 1),
+    }
+}
+
+/// Write a group of cells within a single line, e.g. `r4c5` or `r4c56`
+pub fn cells_name(cells: &[usize]) -> String {
+    let (rows, cols) = (cells.iter().map(|cell| cell / 9 + 1).dedup(), cells.iter().map(|cell| cell % 9 + 1).sorted().dedup());
+    format!("r{}c{}", rows.map(|row| row.to_string()).collect::<String>(), cols.map(|col| col.to_string()).collect::<String>())
+}
+
+fn join<T, F: Fn(&T) -> String>(items: &[T], separator: &str, name: F) -> String {
+    items.iter().map(name).collect::<Vec<_>>().join(separator)
+}
+
+/// Written in Eureka notation, for example `(4)r1c1=(4)r1c5-(4=7)r3c5-(7)r3c89=(7)r2c7`,
+/// where a strong link between two digits in the same cell is written within the cell
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut idx = 0;
+        while idx < self.nodes.len() {
+            let node = &self.nodes[idx];
+            if idx > 0 { write!(f, "{}", if idx % 2 == 1 { "=" } else { "-" })?; }
+            match self.nodes.get(idx + 1) {
+                Some(next) if idx % 2 == 0 && next.cells == node.cells => {
+                    write!(f, "({}={}){}", node.digit, next.digit, cells_name(&node.cells))?;
+                    idx += 2;
+                }
+                _ => {
+                    write!(f, "({}){}", node.digit, cells_name(&node.cells))?;
+                    idx += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Written as the technique, the pattern and then the placements and
+/// eliminations, for example
+/// `Naked Pair 28 in r4 (r4c1,r4c5): r4c2<>2, r4c2<>8, r4c7<>8`.
+/// Chains are written as the technique and the chain, and Almost Locked Sets
+/// as their cells and digits, e.g. `ALS-XZ 3 in r1,c7 (r1c1,r1c2{235}, r5c7{35}): r1c7<>5`.
+impl fmt::Display for Deduction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.technique)?;
+        if let Some(chain) = &self.chain {
+            write!(f, " {}", chain)?;
+        } else {
+            if !self.digits.is_empty() { write!(f, " {}", join(&self.digits, "", |digit| digit.to_string()))?; }
+            if !self.regions.is_empty() { write!(f, " in {}", join(&self.regions, ",", |&region| region_name(region)))?; }
+            if self.sets.is_empty() {
+                if !self.cells.is_empty() { write!(f, " ({})", join(&self.cells, ",", |&cell| cell_name(cell)))?; }
+            } else {
+                // Cells outside of the sets, such as the stem of a Death Blossom, come first
+                let others = self.cells.iter().copied().filter(|cell| self.sets.iter().all(|set| !set.cells.contains(cell))).collect::<Vec<_>>();
+                if !others.is_empty() { write!(f, " {}", join(&others, ",", |&cell| cell_name(cell)))?; }
+                write!(f, " ({})", join(&self.sets, ", ", |set| format!("{}{{{}}}", join(&set.cells, ",", |&cell| cell_name(cell)), join(&set.digits, "", |digit| digit.to_string()))))?;
+            }
+        }
+        let placements = self.placements.iter().map(|&(cell, digit)| format!("{}={}", cell_name(cell), digit));
+        let eliminations = self.eliminations.iter().map(|&(cell, digit)| format!("{}<>{}", cell_name(cell), digit));
+        write!(f, ": {}", placements.chain(eliminations).collect::<Vec<_>>().join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(digit: u8, cells: &[usize]) -> ChainNode {
+        ChainNode { digit, cells: cells.to_vec() }
+    }
+
+    #[test]
+    fn chains_in_eureka_notation() {
+        let chain = Chain { nodes: vec![node(4, &[0]), node(4, &[4]), node(4, &[22]), node(7, &[22]), node(7, &[25, 26]), node(7, &[15])] };
+        assert_eq!(chain.to_string(), "(4)r1c1=(4)r1c5-(4=7)r3c5-(7)r3c89=(7)r2c7");
+
+        let deduction = Deduction {
+            technique: Technique::XyChain(6),
+            regions: Vec::new(),
+            cells: Vec::new(),
+            digits: Vec::new(),
+            placements: Vec::new(),
+            eliminations: vec![(8, 6), (80, 6)],
+            chain: Some(Chain { nodes: vec![node(6, &[44]), node(8, &[44]), node(8, &[5]), node(4, &[5]), node(4, &[20]), node(6, &[20])] }),
+            sets: Vec::new(),
+        };
+        assert_eq!(deduction.to_string(), "XY-Chain (6=8)r5c9-(8=4)r1c6-(4=6)r3c3: r1c9<>6, r9c9<>6");
+    }
+}
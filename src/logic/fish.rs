@@ -0,0 +1,141 @@
+use itertools::Itertools;
+
+use crate::bitmask::Bitmask;
+
+use super::{BasicSolver, Deduction, Technique};
+
+/// The kinds of fish: basic fish have no fins, and finned fish are sashimi
+/// if they would be degenerate without their fins.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(super) enum FishKind {
+    Basic,
+    Finned,
+    Sashimi,
+}
+
+impl BasicSolver {
+
+    /// Find an X-Wing, Swordfish or Jellyfish (of size 2, 3 or 4) of the given kind,
+    /// using either rows or columns as the base lines
+    pub(super) fn find_fish(&self, size: usize, kind: FishKind) -> Option<Deduction> {
+        for val in 0 .. 9 {
+            for rows_are_base in [true, false] {
+                if let Some(deduction) = self.find_fish_in_orientation(val, rows_are_base, size, kind) {
+                    return Some(deduction);
+                }
+            }
+        }
+        None
+    }
+
+    fn find_fish_in_orientation(&self, val: usize, rows_are_base: bool, size: usize, kind: FishKind) -> Option<Deduction> {
+        let cell = |line: usize, pos: usize| if rows_are_base { 9 * line + pos } else { 9 * pos + line };
+        let block = |line: usize, pos: usize| { let cell = cell(line, pos); cell / 27 * 3 + cell % 9 / 3 };
+
+        // The unplaced positions of the digit along each base line
+        let candidates = self.open_positions(val);
+        let lines: [Bitmask<u16>; 9] = std::array::from_fn(|line| {
+            Bitmask::<u16>::from_iter((0 .. 9).filter(|&pos| candidates.contains(cell(line, pos))))
+        });
+        let max_per_line = if kind == FishKind::Basic { size } else { size + 3 };
+        let base_candidates = (0 .. 9).filter(|&line| (1 ..= max_per_line as u32).contains(&lines[line].count_ones())).collect::<Vec<_>>();
+
+        for base in base_candidates.into_iter().combinations(size) {
+            let union = base.iter().map(|&line| lines[line]).fold(Bitmask::<u16>::empty(), |a, b| a | b);
+            let union_size = union.count_ones() as usize;
+
+            let covers: Box<dyn Iterator<Item = Vec<usize>>> = match kind {
+                FishKind::Basic if union_size == size => Box::new(std::iter::once(union.as_bit_iter().collect())),
+                FishKind::Finned | FishKind::Sashimi if union_size > size => Box::new(union.as_bit_iter().combinations(size)),
+                _ => continue,
+            };
+
+            for cover in covers {
+                let cover_mask = Bitmask::<u16>::from_iter(cover.iter().copied());
+
+                // Fins are base candidates outside of the cover lines, and must all be in the same box
+                let fins = base.iter().flat_map(|&line| (lines[line] & !cover_mask).as_bit_iter().map(move |pos| (line, pos))).collect::<Vec<_>>();
+                let fin_block = match fins.first() {
+                    None => None,
+                    Some(&(line, pos)) if fins.iter().all(|&(l, p)| block(l, p) == block(line, pos)) => Some(block(line, pos)),
+                    Some(_) => continue,
+                };
+                if kind != FishKind::Basic {
+                    let is_sashimi = base.iter().any(|&line| (lines[line] & cover_mask).count_ones() <= 1);
+                    if is_sashimi != (kind == FishKind::Sashimi) { continue; }
+                }
+
+                let eliminations = (0 .. 9)
+                    .filter(|line| !base.contains(line))
+                    .flat_map(|line| cover.iter().map(move |&pos| (line, pos)))
+                    .filter(|&(line, pos)| lines[line].contains(pos) && fin_block.is_none_or(|b| block(line, pos) == b))
+                    .map(|(line, pos)| (cell(line, pos), val as u8 + 1))
+                    .sorted()
+                    .collect::<Vec<_>>();
+                if eliminations.is_empty() { continue; }
+
+                let (base_offset, cover_offset) = if rows_are_base { (0, 9) } else { (9, 0) };
+                return Some(Deduction {
+                    technique: match kind {
+                        FishKind::Basic => Technique::Fish(size),
+                        FishKind::Finned => Technique::FinnedFish(size),
+                        FishKind::Sashimi => Technique::SashimiFish(size),
+                    },
+                    regions: base.iter().map(|line| line + base_offset).chain(cover.iter().map(|pos| pos + cover_offset)).collect(),
+                    cells: base.iter().flat_map(|&line| lines[line].as_bit_iter().map(move |pos| cell(line, pos))).sorted().collect(),
+                    digits: vec![val as u8 + 1],
+                    placements: Vec::new(),
+                    eliminations,
+                    chain: None,
+                    sets: Vec::new(),
+                });
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{empty_grid_with, found};
+    use super::*;
+
+    #[test]
+    fn basic_fish() {
+        // 1 is only in c2 and c7 in rows 1 and 5
+        let solver = empty_grid_with(&["r15c1345689<>1"]);
+        assert_eq!(found(&solver, Technique::Fish(2)).as_deref(), Some("X-Wing 1 in r1,r5,c2,c7 (r1c2,r1c7,r5c2,r5c7): r2c2<>1, r2c7<>1, r3c2<>1, r3c7<>1, r4c2<>1, r4c7<>1, r6c2<>1, r6c7<>1, r7c2<>1, r7c7<>1, r8c2<>1, r8c7<>1, r9c2<>1, r9c7<>1"));
+
+        // 2 is only in rows 2, 5 and 8 in columns 1, 4 and 7, and in only two of them in each
+        let solver = empty_grid_with(&["r1346789c1<>2", "r1234679c4<>2", "r1345679c7<>2"]);
+        assert_eq!(found(&solver, Technique::Fish(2)), None);
+        assert_eq!(found(&solver, Technique::Fish(3)).as_deref(), Some("Swordfish 2 in c1,c4,c7,r2,r5,r8 (r2c1,r2c7,r5c1,r5c4,r8c4,r8c7): r2c2<>2, r2c3<>2, r2c5<>2, r2c6<>2, r2c8<>2, r2c9<>2, r5c2<>2, r5c3<>2, r5c5<>2, r5c6<>2, r5c8<>2, r5c9<>2, r8c2<>2, r8c3<>2, r8c5<>2, r8c6<>2, r8c8<>2, r8c9<>2"));
+
+        // 3 is only in c2, c4, c6 and c8 in rows 1, 3, 7 and 9
+        let solver = empty_grid_with(&["r1379c13579<>3"]);
+        assert_eq!(found(&solver, Technique::Fish(3)), None);
+        assert_eq!(found(&solver, Technique::Fish(4)).as_deref(), Some(concat!(
+            "Jellyfish 3 in r1,r3,r7,r9,c2,c4,c6,c8 (r1c2,r1c4,r1c6,r1c8,r3c2,r3c4,r3c6,r3c8,r7c2,r7c4,r7c6,r7c8,r9c2,r9c4,r9c6,r9c8): ",
+            "r2c2<>3, r2c4<>3, r2c6<>3, r2c8<>3, r4c2<>3, r4c4<>3, r4c6<>3, r4c8<>3, r5c2<>3, r5c4<>3, r5c6<>3, r5c8<>3, ",
+            "r6c2<>3, r6c4<>3, r6c6<>3, r6c8<>3, r8c2<>3, r8c4<>3, r8c6<>3, r8c8<>3",
+        )));
+    }
+
+    #[test]
+    fn finned_fish() {
+        // An X-Wing on 1 in rows 1 and 5, but for the fin r5c8
+        let solver = empty_grid_with(&["r1c1345689<>1", "r5c134569<>1"]);
+        assert_eq!(found(&solver, Technique::Fish(2)), None);
+        assert_eq!(found(&solver, Technique::FinnedFish(2)).as_deref(), Some("Finned X-Wing 1 in r1,r5,c2,c7 (r1c2,r1c7,r5c2,r5c7,r5c8): r4c7<>1, r6c7<>1"));
+        assert_eq!(found(&solver, Technique::SashimiFish(2)), None);
+    }
+
+    #[test]
+    fn sashimi_fish() {
+        // As above, but r5c7 is missing from the X-Wing
+        let solver = empty_grid_with(&["r1c1345689<>1", "r5c1345679<>1"]);
+        assert_eq!(found(&solver, Technique::Fish(2)), None);
+        assert_eq!(found(&solver, Technique::SashimiFish(2)).as_deref(), Some("Sashimi X-Wing 1 in r1,r5,c2,c7 (r1c2,r1c7,r5c2,r5c8): r4c7<>1, r6c7<>1"));
+        assert_eq!(found(&solver, Technique::FinnedFish(2)), None);
+    }
+}
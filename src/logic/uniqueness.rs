@@ -0,0 +1,314 @@
+use itertools::Itertools;
+
+use crate::bitmask::Bitmask;
+
+use super::{eliminations_of, placement, BasicSolver, Deduction, Technique, CELL_REGIONS, PEERS, REGIONS};
+
+/// The four cells of a rectangle in two rows, two columns and two boxes, in the
+/// order top left, top right, bottom left, bottom right
+fn rectangles() -> impl Iterator<Item = [usize; 4]> {
+    (0 .. 9).tuple_combinations().cartesian_product((0 .. 9).tuple_combinations())
+        .filter(|&((row1, row2), (col1, col2))| (row1 / 3 == row2 / 3) != (col1 / 3 == col2 / 3))
+        .map(|((row1, row2), (col1, col2))| [9 * row1 + col1, 9 * row1 + col2, 9 * row2 + col1, 9 * row2 + col2])
+}
+
+/// The eliminations made using a Unique Rectangle, and any cells outside of the
+/// rectangle which were needed to make them
+type RectangleEliminations = (Vec<(usize, u8)>, Vec<usize>);
+
+/// Whether two cells are diagonally opposite in a rectangle
+fn diagonal(a: usize, b: usize) -> bool {
+    a / 9 != b / 9 && a % 9 != b % 9
+}
+
+/// The cells which see all of the given cells
+fn seen_by_all(cells: &[usize]) -> Bitmask<u128> {
+    cells.iter().fold(!Bitmask::<u128>::empty(), |seen, &cell| seen & PEERS[cell])
+}
+
+impl BasicSolver {
+
+    /// Find a Unique Rectangle of the given type: four unplaced cells in two rows,
+    /// two columns and two boxes which all have the digits A and B as candidates.
+    /// If all four could only be A or B then the puzzle would have two solutions,
+    /// so assuming it doesn't, some of the extra candidates must be true.
+    pub(super) fn find_unique_rectangle(&self, kind: usize) -> Option<Deduction> {
+        if !self.uniqueness { return None; }
+        for cells in rectangles() {
+            if cells.iter().any(|&cell| !self.unplaced.contains(cell)) { continue; }
+            let common = cells.iter().map(|&cell| self.cell_candidates(cell)).fold(!Bitmask::<u16>::empty(), |a, b| a & b);
+            for (a, b) in common.as_bit_iter().collect::<Vec<_>>().into_iter().tuple_combinations() {
+                let pair = Bitmask::<u16>::from_iter([a, b].into_iter());
+                let (floor, roof): (Vec<usize>, Vec<usize>) = cells.iter().partition(|&&cell| self.cell_candidates(cell) == pair);
+                let found = match kind {
+                    1 => self.unique_rectangle_type_1(pair, &roof),
+                    2 | 5 => self.unique_rectangle_type_2_or_5(kind, pair, &roof),
+                    3 => self.unique_rectangle_type_3(pair, &roof),
+                    4 => self.unique_rectangle_type_4(pair, &roof),
+                    6 => self.unique_rectangle_type_6(pair, &floor, &roof),
+                    _ => None,
+                };
+                if let Some((eliminations, extra_cells)) = found {
+                    return Some(Deduction {
+                        technique: Technique::UniqueRectangle(kind),
+                        regions: Vec::new(),
+                        cells: cells.into_iter().chain(extra_cells).sorted().collect(),
+                        digits: vec![a as u8 + 1, b as u8 + 1],
+                        placements: Vec::new(),
+                        eliminations,
+                        chain: None,
+                        sets: Vec::new(),
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// Type 1: only one cell has extra candidates, so it can't be A or B
+    fn unique_rectangle_type_1(&self, pair: Bitmask<u16>, roof: &[usize]) -> Option<RectangleEliminations> {
+        let &[cell] = roof else { return None; };
+        Some((pair.as_bit_iter().map(|val| (cell, val as u8 + 1)).collect(), Vec::new()))
+    }
+
+    /// Type 2: two cells in a line share a single extra candidate C, so one of them is C.
+    /// Type 5: as type 2, but with the extra candidate in two diagonal cells or in three cells.
+    fn unique_rectangle_type_2_or_5(&self, kind: usize, pair: Bitmask<u16>, roof: &[usize]) -> Option<RectangleEliminations> {
+        let is_type_2 = roof.len() == 2 && !diagonal(roof[0], roof[1]);
+        if (kind == 2) != is_type_2 || !(2 ..= 3).contains(&roof.len()) { return None; }
+        let extra = self.cell_candidates(roof[0]) & !pair;
+        if extra.count_ones() != 1 || roof.iter().any(|&cell| self.cell_candidates(cell) != pair | extra) { return None; }
+        let val = extra.as_bit_iter().next().unwrap();
+        let eliminated = seen_by_all(roof) & self.open_positions(val);
+        if eliminated.is_empty() { return None; }
+        Some((eliminations_of(eliminated, val), Vec::new()))
+    }
+
+    /// Type 3: two cells in a line have extra candidates, which together with some other
+    /// cells of a region containing both form a naked subset, eliminating its digits
+    /// from the rest of the region
+    fn unique_rectangle_type_3(&self, pair: Bitmask<u16>, roof: &[usize]) -> Option<RectangleEliminations> {
+        let &[roof1, roof2] = roof else { return None; };
+        if diagonal(roof1, roof2) { return None; }
+        let extra = (self.cell_candidates(roof1) | self.cell_candidates(roof2)) & !pair;
+        for region in (CELL_REGIONS[roof1] & CELL_REGIONS[roof2]).as_bit_iter() {
+            let others = REGIONS[region] & self.unplaced & !Bitmask::<u128>::from_iter([roof1, roof2].into_iter());
+            for subset in others.as_subset_iter() {
+                let size = subset.count_ones() as usize;
+                if !(1 ..= 3).contains(&size) { continue; }
+                let values = subset.as_bit_iter().fold(extra, |values, cell| values | self.cell_candidates(cell));
+                if values.count_ones() as usize != size + 1 { continue; }
+                let eliminations = (others & !subset).as_bit_iter()
+                    .flat_map(|cell| (self.cell_candidates(cell) & values).as_bit_iter().map(move |val| (cell, val as u8 + 1)))
+                    .sorted()
+                    .collect::<Vec<_>>();
+                if eliminations.is_empty() { continue; }
+                return Some((eliminations, subset.as_bit_iter().collect()));
+            }
+        }
+        None
+    }
+
+    /// Type 4: two cells in a line have extra candidates, and are the only places
+    /// for A in a region containing both, so neither of them can be B
+    fn unique_rectangle_type_4(&self, pair: Bitmask<u16>, roof: &[usize]) -> Option<RectangleEliminations> {
+        let &[roof1, roof2] = roof else { return None; };
+        if diagonal(roof1, roof2) { return None; }
+        let roof_cells = Bitmask::<u128>::from_iter([roof1, roof2].into_iter());
+        for region in (CELL_REGIONS[roof1] & CELL_REGIONS[roof2]).as_bit_iter() {
+            let (a, b) = pair.as_bit_iter().collect_tuple().unwrap();
+            for (a, b) in [(a, b), (b, a)] {
+                if REGIONS[region] & self.open_positions(a) == roof_cells {
+                    return Some((eliminations_of(roof_cells, b), Vec::new()));
+                }
+            }
+        }
+        None
+    }
+
+    /// Type 6: two diagonal cells are exactly AB, and A is only in the rectangle in
+    /// both of its rows or both of its columns, so the other two cells can't be A
+    fn unique_rectangle_type_6(&self, pair: Bitmask<u16>, floor: &[usize], roof: &[usize]) -> Option<RectangleEliminations> {
+        let (&[floor1, floor2], &[roof1, roof2]) = (floor, roof) else { return None; };
+        if !diagonal(floor1, floor2) { return None; }
+        let cells = Bitmask::<u128>::from_iter([floor1, floor2, roof1, roof2].into_iter());
+        let roof_cells = Bitmask::<u128>::from_iter([roof1, roof2].into_iter());
+        for val in pair.as_bit_iter() {
+            let positions = self.open_positions(val);
+            let only_in_rectangle = |lines: [usize; 2]| lines.iter().all(|&line| (REGIONS[line] & positions & !cells).is_empty());
+            if only_in_rectangle([floor1 / 9, floor2 / 9]) || only_in_rectangle([9 + floor1 % 9, 9 + floor2 % 9]) {
+                return Some((eliminations_of(roof_cells, val), Vec::new()));
+            }
+        }
+        None
+    }
+
+    /// Find a BUG+1: every unplaced cell but one has exactly two candidates, and
+    /// that one has three. Without it the puzzle would have two solutions, so it must
+    /// hold the digit which appears three times in its regions.
+    #[allow(clippy::needless_range_loop)]
+    pub(super) fn find_bug_plus_one(&self) -> Option<Deduction> {
+        if !self.uniqueness { return None; }
+        let mut extra_cells = self.unplaced.as_bit_iter().filter(|&cell| self.cell_candidates(cell).count_ones() != 2);
+        let (Some(cell), None) = (extra_cells.next(), extra_cells.next()) else { return None; };
+        if self.cell_candidates(cell).count_ones() != 3 { return None; }
+
+        // Every digit must appear twice in every region, except for the extra digit
+        // in the regions of the extra cell, where it appears three times
+        let mut extra_val = None;
+        for val in 0 .. 9 {
+            let positions = self.open_positions(val);
+            for region in 0 .. 27 {
+                match (REGIONS[region] & positions).count_ones() {
+                    0 | 2 => {}
+                    3 if CELL_REGIONS[cell].contains(region) && positions.contains(cell) && extra_val.is_none_or(|extra| extra == val) => extra_val = Some(val),
+                    _ => return None,
+                }
+            }
+        }
+        extra_val.map(|val| placement(Technique::BugPlusOne, None, cell, val))
+    }
+
+    /// Find an Avoidable Rectangle of the given type: four cells in two rows, two columns
+    /// and two boxes, none of them givens, some of which have been solved. If they could
+    /// end up as A and B in a pattern that could be swapped then the puzzle would have
+    /// two solutions, so the remaining cells must avoid it.
+    pub(super) fn find_avoidable_rectangle(&self, kind: usize) -> Option<Deduction> {
+        if !self.uniqueness { return None; }
+        for cells in rectangles() {
+            if cells.iter().any(|&cell| self.givens.contains(cell)) { continue; }
+            let (solved, unsolved): (Vec<usize>, Vec<usize>) = cells.iter().partition(|&&cell| !self.unplaced.contains(cell));
+            let found = match (kind, solved.as_slice(), unsolved.as_slice()) {
+                // Type 1: three cells are solved, with the two in line with the last cell
+                // sharing a value, so the last cell can't match the cell opposite it
+                (1, _, &[cell]) => {
+                    let (opposite, sides): (Vec<usize>, Vec<usize>) = solved.iter().partition(|&&other| diagonal(cell, other));
+                    let (opposite_val, side_val) = (self.placed_value(opposite[0]), self.placed_value(sides[0]));
+                    let is_pattern = self.placed_value(sides[1]) == side_val && opposite_val != side_val;
+                    (is_pattern && self.candidate_positions[opposite_val].contains(cell)).then(|| eliminations_of(Bitmask::<u128>::singleton(cell), opposite_val))
+                }
+                // Type 2: two cells in a line are solved as A and B, and the other two are BC and AC
+                // in the same columns (or rows) as A and B, so one of them must be C
+                (2, &[solved1, solved2], &[unsolved1, unsolved2]) if !diagonal(solved1, solved2) => {
+                    let (val1, val2) = (self.placed_value(solved1), self.placed_value(solved2));
+                    // The unsolved cell in line with each solved cell, away from the solved line
+                    let (across1, across2) = if diagonal(solved1, unsolved1) { (unsolved2, unsolved1) } else { (unsolved1, unsolved2) };
+                    let (candidates1, candidates2) = (self.cell_candidates(across1), self.cell_candidates(across2));
+                    let extra = candidates1 & !Bitmask::<u16>::singleton(val2);
+                    let is_pattern = candidates1.count_ones() == 2 && candidates1.contains(val2) && extra.count_ones() == 1
+                        && !extra.contains(val1) && candidates2 == extra | Bitmask::<u16>::singleton(val1);
+                    is_pattern.then(|| {
+                        let val = extra.as_bit_iter().next().unwrap();
+                        eliminations_of(seen_by_all(&[across1, across2]) & self.open_positions(val), val)
+                    })
+                }
+                _ => None,
+            };
+            if let Some(eliminations) = found.filter(|eliminations| !eliminations.is_empty()) {
+                let digits = solved.iter().map(|&cell| self.placed_value(cell) as u8 + 1).sorted().dedup().collect();
+                return Some(Deduction {
+                    technique: Technique::AvoidableRectangle(kind),
+                    regions: Vec::new(),
+                    cells: cells.into_iter().sorted().collect(),
+                    digits,
+                    placements: Vec::new(),
+                    eliminations,
+                    chain: None,
+                    sets: Vec::new(),
+                });
+            }
+        }
+        None
+    }
+
+    /// The value placed in a solved cell
+    fn placed_value(&self, cell: usize) -> usize {
+        (0 .. 9).find(|&val| self.candidate_positions[val].contains(cell)).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{empty_grid_with, found, solver_with};
+    use super::super::uniqueness_techniques;
+    use super::*;
+
+    /// The deduction found with the given technique once the solver assumes that the
+    /// puzzle has a unique solution, checking that no uniqueness technique finds
+    /// anything before then
+    fn found_assuming_uniqueness(mut solver: BasicSolver, technique: Technique) -> Option<String> {
+        for other in uniqueness_techniques() {
+            assert_eq!(found(&solver, other), None);
+        }
+        solver.assume_uniqueness();
+        found(&solver, technique)
+    }
+
+    #[test]
+    fn unique_rectangle_type_1() {
+        // r1c1, r1c4 and r2c1 are 12, so r2c4 can't be either
+        let solver = empty_grid_with(&["r12c1<>3456789", "r1c4<>3456789"]);
+        assert_eq!(found_assuming_uniqueness(solver, Technique::UniqueRectangle(1)).as_deref(), Some("Unique Rectangle Type 1 12 (r1c1,r1c4,r2c1,r2c4): r2c4<>1, r2c4<>2"));
+    }
+
+    #[test]
+    fn unique_rectangle_type_2() {
+        // r2c1 and r2c4 are both 123
+        let solver = empty_grid_with(&["r1c14<>3456789", "r2c14<>456789"]);
+        assert_eq!(found_assuming_uniqueness(solver, Technique::UniqueRectangle(2)).as_deref(), Some("Unique Rectangle Type 2 12 (r1c1,r1c4,r2c1,r2c4): r2c2<>3, r2c3<>3, r2c5<>3, r2c6<>3, r2c7<>3, r2c8<>3, r2c9<>3"));
+    }
+
+    #[test]
+    fn unique_rectangle_type_3() {
+        // The extra candidates 3 and 4 form a naked pair with r2c7
+        let solver = empty_grid_with(&["r1c14<>3456789", "r2c1<>456789", "r2c4<>356789", "r2c7<>1256789"]);
+        assert_eq!(found_assuming_uniqueness(solver, Technique::UniqueRectangle(3)).as_deref(), Some("Unique Rectangle Type 3 12 (r1c1,r1c4,r2c1,r2c4,r2c7): r2c2<>3, r2c2<>4, r2c3<>3, r2c3<>4, r2c5<>3, r2c5<>4, r2c6<>3, r2c6<>4, r2c8<>3, r2c8<>4, r2c9<>3, r2c9<>4"));
+    }
+
+    #[test]
+    fn unique_rectangle_type_4() {
+        // 1 is only in the rectangle in row 2
+        let solver = empty_grid_with(&["r1c14<>3456789", "r2c2356789<>1"]);
+        assert_eq!(found_assuming_uniqueness(solver, Technique::UniqueRectangle(4)).as_deref(), Some("Unique Rectangle Type 4 12 (r1c1,r1c4,r2c1,r2c4): r2c1<>2, r2c4<>2"));
+    }
+
+    #[test]
+    fn unique_rectangle_type_5() {
+        // The diagonal cells r1c4 and r2c1 are both 123
+        let solver = empty_grid_with(&["r1c1<>3456789", "r2c4<>3456789", "r1c4<>456789", "r2c1<>456789"]);
+        assert_eq!(found_assuming_uniqueness(solver, Technique::UniqueRectangle(5)).as_deref(), Some("Unique Rectangle Type 5 12 (r1c1,r1c4,r2c1,r2c4): r1c2<>3, r1c3<>3, r2c5<>3, r2c6<>3"));
+    }
+
+    #[test]
+    fn unique_rectangle_type_6() {
+        // 1 is only in the rectangle in rows 1 and 2
+        let solver = empty_grid_with(&["r1c1<>3456789", "r2c4<>3456789", "r12c2356789<>1"]);
+        assert_eq!(found_assuming_uniqueness(solver, Technique::UniqueRectangle(6)).as_deref(), Some("Unique Rectangle Type 6 12 (r1c1,r1c4,r2c1,r2c4): r1c4<>1, r2c1<>1"));
+    }
+
+    #[test]
+    fn bug_plus_one() {
+        // Every unplaced cell but r3c9 has two candidates, and 6 is in r3c9 and two other
+        // cells in each of its regions
+        let solver = solver_with("417309805032158047058704310825437100791586030346912758289643571573291080164875003", &[]);
+        assert_eq!(found_assuming_uniqueness(solver, Technique::BugPlusOne).as_deref(), Some("BUG+1 6 (r3c9): r3c9=6"));
+    }
+
+    #[test]
+    fn avoidable_rectangle_type_1() {
+        // r1c4 and r2c1 were solved as 2 and r1c1 as 1, so r2c4 can't be 1
+        let solver = empty_grid_with(&["r1c1=1", "r1c4=2", "r2c1=2"]);
+        assert_eq!(found_assuming_uniqueness(solver, Technique::AvoidableRectangle(1)).as_deref(), Some("Avoidable Rectangle Type 1 12 (r1c1,r1c4,r2c1,r2c4): r2c4<>1"));
+
+        // Givens can be part of a solution which is swapped with another
+        let solver = solver_with(&format!("1..2.....2{}", ".".repeat(71)), &[]);
+        assert_eq!(found_assuming_uniqueness(solver, Technique::AvoidableRectangle(1)), None);
+    }
+
+    #[test]
+    fn avoidable_rectangle_type_2() {
+        // r1c1 and r1c4 were solved as 1 and 2, and below them r2c1 is 23 and r2c4 is 13
+        let solver = empty_grid_with(&["r1c1=1", "r1c4=2", "r2c14<>456789"]);
+        assert_eq!(found_assuming_uniqueness(solver, Technique::AvoidableRectangle(2)).as_deref(), Some("Avoidable Rectangle Type 2 12 (r1c1,r1c4,r2c1,r2c4): r2c2<>3, r2c3<>3, r2c5<>3, r2c6<>3, r2c7<>3, r2c8<>3, r2c9<>3"));
+    }
+}
@@ -0,0 +1,312 @@
+use itertools::Itertools;
+
+use crate::bitmask::Bitmask;
+
+use super::{eliminations_of, BasicSolver, Deduction, Technique, PEERS, REGIONS};
+
+impl BasicSolver {
+
+    /// Find an XY-Wing: a bivalue pivot XY seeing bivalue pincers XZ and YZ.
+    /// One of the pincers must be Z, so Z is eliminated from every cell seeing both.
+    pub(super) fn find_xy_wing(&self) -> Option<Deduction> {
+        let bivalues = self.bivalue_cells();
+        for &pivot in &bivalues {
+            let pivot_values = self.cell_candidates(pivot);
+            let pincers = bivalues.iter().copied()
+                .filter(|&cell| PEERS[pivot].contains(cell) && (self.cell_candidates(cell) & pivot_values).count_ones() == 1)
+                .collect::<Vec<_>>();
+            for (a, b) in pincers.into_iter().tuple_combinations() {
+                let (a_values, b_values) = (self.cell_candidates(a), self.cell_candidates(b));
+                let shared = a_values & b_values & !pivot_values;
+                if shared.count_ones() != 1 || (a_values | b_values).count_ones() != 3 { continue; }
+
+                let z = shared.as_bit_iter().next().unwrap();
+                let eliminated = PEERS[a] & PEERS[b] & self.open_positions(z);
+                if eliminated.is_empty() { continue; }
+                return Some(wing(Technique::XyWing, vec![pivot, a, b], pivot_values | shared, eliminated, z));
+            }
+        }
+        None
+    }
+
+    /// Find an XYZ-Wing: a pivot XYZ seeing bivalue pincers XZ and YZ. One of the
+    /// three cells must be Z, so Z is eliminated from every cell seeing all of them.
+    pub(super) fn find_xyz_wing(&self) -> Option<Deduction> {
+        let bivalues = self.bivalue_cells();
+        for pivot in self.unplaced.as_bit_iter() {
+            let pivot_values = self.cell_candidates(pivot);
+            if pivot_values.count_ones() != 3 { continue; }
+            let pincers = bivalues.iter().copied()
+                .filter(|&cell| PEERS[pivot].contains(cell) && (self.cell_candidates(cell) & !pivot_values).is_empty())
+                .collect::<Vec<_>>();
+            for (a, b) in pincers.into_iter().tuple_combinations() {
+                let shared = self.cell_candidates(a) & self.cell_candidates(b);
+                if shared.count_ones() != 1 { continue; }
+
+                let z = shared.as_bit_iter().next().unwrap();
+                let eliminated = PEERS[pivot] & PEERS[a] & PEERS[b] & self.open_positions(z);
+                if eliminated.is_empty() { continue; }
+                return Some(wing(Technique::XyzWing, vec![pivot, a, b], pivot_values, eliminated, z));
+            }
+        }
+        None
+    }
+
+    /// Find a W-Wing: two bivalue cells XY which don't see each other, joined by a
+    /// conjugate pair on X with one end seeing each of them. One of the two cells
+    /// must be Y, so Y is eliminated from every cell seeing both.
+    pub(super) fn find_w_wing(&self) -> Option<Deduction> {
+        let bivalues = self.bivalue_cells();
+        for (a, b) in bivalues.into_iter().tuple_combinations() {
+            let values = self.cell_candidates(a);
+            if values != self.cell_candidates(b) || PEERS[a].contains(b) { continue; }
+            let (x, y) = values.as_bit_iter().collect_tuple().unwrap();
+            for (x, y) in [(x, y), (y, x)] {
+                let eliminated = PEERS[a] & PEERS[b] & self.open_positions(y);
+                if eliminated.is_empty() { continue; }
+                for (region, end1, end2) in self.conjugate_pairs(x) {
+                    if [end1, end2].iter().any(|end| [a, b].contains(end)) { continue; }
+                    let joined = (PEERS[a].contains(end1) && PEERS[b].contains(end2)) || (PEERS[a].contains(end2) && PEERS[b].contains(end1));
+                    if !joined { continue; }
+                    return Some(Deduction {
+                        regions: vec![region],
+                        ..wing(Technique::WWing, vec![a, b, end1, end2], values, eliminated, y)
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// Find a Skyscraper: conjugate pairs in two parallel lines with one end of
+    /// each in the same cross line. One of the other two ends must hold the digit,
+    /// so it is eliminated from every cell seeing both of them.
+    pub(super) fn find_skyscraper(&self) -> Option<Deduction> {
+        for val in 0 .. 9 {
+            for lines in [0 .. 9, 9 .. 18] {
+                let position = |cell: usize| if lines.start == 0 { cell % 9 } else { cell / 9 };
+                let pairs = self.conjugate_pairs(val).into_iter().filter(|(region, _, _)| lines.contains(region)).collect::<Vec<_>>();
+                for (&(region1, a1, a2), &(region2, b1, b2)) in pairs.iter().tuple_combinations() {
+                    for ((base1, end1), (base2, end2)) in [((a1, a2), (b1, b2)), ((a1, a2), (b2, b1)), ((a2, a1), (b1, b2)), ((a2, a1), (b2, b1))] {
+                        if position(base1) != position(base2) || position(end1) == position(end2) { continue; }
+                        let eliminated = PEERS[end1] & PEERS[end2] & self.open_positions(val);
+                        if eliminated.is_empty() { continue; }
+                        return Some(single_digit(Technique::Skyscraper, vec![region1, region2], vec![base1, end1, base2, end2], eliminated, val));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Find a 2-String Kite: conjugate pairs in a row and a column with one end of
+    /// each in the same box. One of the other two ends must hold the digit, so it is
+    /// eliminated from every cell seeing both of them.
+    pub(super) fn find_two_string_kite(&self) -> Option<Deduction> {
+        for val in 0 .. 9 {
+            let pairs = self.conjugate_pairs(val);
+            let row_pairs = pairs.iter().filter(|(region, _, _)| *region < 9);
+            let col_pairs = pairs.iter().filter(|(region, _, _)| (9 .. 18).contains(region));
+            for (&(row, a1, a2), &(col, b1, b2)) in row_pairs.cartesian_product(col_pairs) {
+                for ((base1, end1), (base2, end2)) in [((a1, a2), (b1, b2)), ((a1, a2), (b2, b1)), ((a2, a1), (b1, b2)), ((a2, a1), (b2, b1))] {
+                    if [base1, end1].iter().any(|cell| [base2, end2].contains(cell)) || box_of(base1) != box_of(base2) { continue; }
+                    let eliminated = PEERS[end1] & PEERS[end2] & self.open_positions(val);
+                    if eliminated.is_empty() { continue; }
+                    return Some(single_digit(Technique::TwoStringKite, vec![row, col], vec![base1, end1, base2, end2], eliminated, val));
+                }
+            }
+        }
+        None
+    }
+
+    /// Find an Empty Rectangle: a box whose candidates for a digit all lie in one
+    /// row and one column, together with a conjugate pair in a line outside the box
+    /// which has one end in the box's column (or row). Whichever end of the pair holds
+    /// the digit, it is eliminated where the other end's line meets the box's row (or column).
+    pub(super) fn find_empty_rectangle(&self) -> Option<Deduction> {
+        for val in 0 .. 9 {
+            let positions = self.open_positions(val);
+            let pairs = self.conjugate_pairs(val);
+            for block in 0 .. 9 {
+                let box_cells = REGIONS[18 + block] & positions;
+                let (band, stack) = (block / 3 * 3 .. block / 3 * 3 + 3, block % 3 * 3 .. block % 3 * 3 + 3);
+                for (row, col) in band.clone().cartesian_product(stack.clone()) {
+                    let (row_cells, col_cells) = (REGIONS[row], REGIONS[9 + col]);
+                    if (box_cells & !(row_cells | col_cells)).is_not_empty()
+                        || (box_cells & row_cells & !col_cells).is_empty()
+                        || (box_cells & col_cells & !row_cells).is_empty() { continue; }
+
+                    for &(region, a, b) in &pairs {
+                        // A conjugate pair in a row outside the band, with one end in the box's column,
+                        // or in a column outside the stack, with one end in the box's row
+                        let target = if region < 9 && !band.contains(&region) {
+                            [(a, b), (b, a)].into_iter().find(|&(end, other)| end % 9 == col && !stack.contains(&(other % 9))).map(|(_, other)| 9 * row + other % 9)
+                        } else if (9 .. 18).contains(&region) && !stack.contains(&(region - 9)) {
+                            [(a, b), (b, a)].into_iter().find(|&(end, other)| end / 9 == row && !band.contains(&(other / 9))).map(|(_, other)| 9 * (other / 9) + col)
+                        } else {
+                            None
+                        };
+                        let Some(target) = target.filter(|&target| positions.contains(target)) else { continue; };
+
+                        let cells = box_cells.as_bit_iter().chain([a, b]).collect();
+                        return Some(single_digit(Technique::EmptyRectangle, vec![18 + block, region], cells, Bitmask::<u128>::singleton(target), val));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Find a Simple Coloring: the cells joined by a chain of conjugate pairs on a
+    /// digit, colored alternately, so that all the cells of one of the two colors hold
+    /// the digit. If two cells of the same color see each other then that color is false,
+    /// and otherwise the digit is eliminated from any cell that sees both colors.
+    pub(super) fn find_simple_coloring(&self) -> Option<Deduction> {
+        for val in 0 .. 9 {
+            let positions = self.open_positions(val);
+            let pairs = self.conjugate_pairs(val);
+            let mut uncolored = Bitmask::<u128>::from_iter(pairs.iter().flat_map(|&(_, a, b)| [a, b]));
+
+            while let Some(start) = uncolored.as_bit_iter().next() {
+                // Color the whole chain containing the first uncolored cell
+                let mut colors = [Bitmask::<u128>::empty(); 2];
+                let mut stack = vec![(start, 0)];
+                uncolored.unset(start);
+                while let Some((cell, color)) = stack.pop() {
+                    colors[color].set(cell);
+                    for &(_, a, b) in &pairs {
+                        let next = if a == cell { b } else if b == cell { a } else { continue };
+                        if uncolored.contains(next) {
+                            uncolored.unset(next);
+                            stack.push((next, 1 - color));
+                        }
+                    }
+                }
+
+                let chain = colors[0] | colors[1];
+                let wrapped = colors.into_iter().find(|&cells| cells.as_bit_iter().any(|cell| (PEERS[cell] & cells).is_not_empty()));
+                let eliminated = wrapped.unwrap_or_else(|| {
+                    Bitmask::<u128>::from_iter((positions & !chain).as_bit_iter()
+                        .filter(|&cell| (PEERS[cell] & colors[0]).is_not_empty() && (PEERS[cell] & colors[1]).is_not_empty()))
+                });
+                if eliminated.is_empty() { continue; }
+                return Some(single_digit(Technique::SimpleColoring, Vec::new(), chain.as_bit_iter().collect(), eliminated, val));
+            }
+        }
+        None
+    }
+}
+
+/// A wing on several digits, eliminating a single digit from the given cells
+fn wing(technique: Technique, cells: Vec<usize>, values: Bitmask<u16>, eliminated: Bitmask<u128>, val: usize) -> Deduction {
+    Deduction {
+        technique,
+        regions: Vec::new(),
+        cells: cells.into_iter().sorted().collect(),
+        digits: values.as_bit_iter().map(|val| val as u8 + 1).collect(),
+        placements: Vec::new(),
+        eliminations: eliminations_of(eliminated, val),
+        chain: None,
+        sets: Vec::new(),
+    }
+}
+
+/// A pattern on a single digit, eliminating it from the given cells
+fn single_digit(technique: Technique, regions: Vec<usize>, cells: Vec<usize>, eliminated: Bitmask<u128>, val: usize) -> Deduction {
+    Deduction {
+        technique,
+        regions,
+        cells: cells.into_iter().sorted().collect(),
+        digits: vec![val as u8 + 1],
+        placements: Vec::new(),
+        eliminations: eliminations_of(eliminated, val),
+        chain: None,
+        sets: Vec::new(),
+    }
+}
+
+/// The box containing a cell, numbered from 0 to 8
+fn box_of(cell: usize) -> usize {
+    cell / 27 * 3 + cell % 9 / 3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{empty_grid_with, found};
+    use super::*;
+
+    #[test]
+    fn xy_wing() {
+        // r1c1 is 12, with the pincers r1c5 13 and r5c1 23
+        let solver = empty_grid_with(&["r1c1<>3456789", "r1c5<>2456789", "r5c1<>1456789"]);
+        assert_eq!(found(&solver, Technique::XyWing).as_deref(), Some("XY-Wing 123 (r1c1,r1c5,r5c1): r5c5<>3"));
+
+        // The pincers don't share a digit
+        let solver = empty_grid_with(&["r1c1<>3456789", "r1c5<>2456789", "r5c1<>1356789"]);
+        assert_eq!(found(&solver, Technique::XyWing), None);
+    }
+
+    #[test]
+    fn xyz_wing() {
+        // r1c1 is 123, with the pincers r1c5 13 and r2c2 23
+        let solver = empty_grid_with(&["r1c1<>456789", "r1c5<>2456789", "r2c2<>1456789"]);
+        assert_eq!(found(&solver, Technique::XyzWing).as_deref(), Some("XYZ-Wing 123 (r1c1,r1c5,r2c2): r1c2<>3, r1c3<>3"));
+
+        // The pincers share two digits
+        let solver = empty_grid_with(&["r1c1<>456789", "r1c5<>2456789", "r2c2<>2456789"]);
+        assert_eq!(found(&solver, Technique::XyzWing), None);
+    }
+
+    #[test]
+    fn w_wing() {
+        // Two cells with 12, and a conjugate pair on 1 in row 9 which both of them see
+        let solver = empty_grid_with(&["r1c1<>3456789", "r5c5<>3456789", "r9c2346789<>1"]);
+        assert_eq!(found(&solver, Technique::WWing).as_deref(), Some("W-Wing 12 in r9 (r1c1,r5c5,r9c1,r9c5): r1c5<>2, r5c1<>2"));
+
+        let solver = empty_grid_with(&["r1c1<>3456789", "r5c5<>3456789"]);
+        assert_eq!(found(&solver, Technique::WWing), None);
+    }
+
+    #[test]
+    fn skyscraper() {
+        // 1 is only in c1 and c5 in row 1, and only in c1 and c6 in row 5
+        let solver = empty_grid_with(&["r1c2346789<>1", "r5c2345789<>1"]);
+        assert_eq!(found(&solver, Technique::Skyscraper).as_deref(), Some("Skyscraper 1 in r1,r5 (r1c1,r1c5,r5c1,r5c6): r2c6<>1, r3c6<>1, r4c5<>1, r6c5<>1"));
+
+        // The conjugate pairs don't line up at either end
+        let solver = empty_grid_with(&["r1c2346789<>1", "r5c1345789<>1"]);
+        assert_eq!(found(&solver, Technique::Skyscraper), None);
+    }
+
+    #[test]
+    fn two_string_kite() {
+        // 1 is only in c2 and c7 in row 1, and only in r3 and r8 in column 1
+        let solver = empty_grid_with(&["r1c1345689<>1", "r1245679c1<>1"]);
+        assert_eq!(found(&solver, Technique::TwoStringKite).as_deref(), Some("2-String Kite 1 in r1,c1 (r1c2,r1c7,r3c1,r8c1): r8c7<>1"));
+
+        // Neither end of the pair in column 1 is in the box of the pair in row 1
+        let solver = empty_grid_with(&["r1c1345689<>1", "r1235679c1<>1"]);
+        assert_eq!(found(&solver, Technique::TwoStringKite), None);
+    }
+
+    #[test]
+    fn empty_rectangle() {
+        // 1 is only in row 5 and column 5 in box 5, and in row 2 it's only in c5 and c8
+        let solver = empty_grid_with(&["r46c46<>1", "r2c1234679<>1"]);
+        assert_eq!(found(&solver, Technique::EmptyRectangle).as_deref(), Some("Empty Rectangle 1 in b5,r2 (r2c5,r2c8,r4c5,r5c4,r5c5,r5c6,r6c5): r5c8<>1"));
+
+        let solver = empty_grid_with(&["r46c46<>1"]);
+        assert_eq!(found(&solver, Technique::EmptyRectangle), None);
+    }
+
+    #[test]
+    fn simple_coloring() {
+        // 1 is in conjugate pairs r1c1-r1c5, r1c5-r4c5 and r4c5-r4c2
+        let solver = empty_grid_with(&["r1c2346789<>1", "r2356789c5<>1", "r4c1346789<>1"]);
+        assert_eq!(found(&solver, Technique::SimpleColoring).as_deref(), Some("Simple Coloring 1 (r1c1,r1c5,r4c2,r4c5): r2c2<>1, r3c2<>1, r5c1<>1, r6c1<>1"));
+
+        // Without the pair in row 4, no cell outside row 1 sees both colours
+        let solver = empty_grid_with(&["r1c2346789<>1", "r2356789c5<>1"]);
+        assert_eq!(found(&solver, Technique::SimpleColoring), None);
+    }
+}
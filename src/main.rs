@@ -1,54 +1,295 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::PathBuf;
+use std::process::exit;
+use std::time::Duration;
+
+use clap::{ArgGroup, Args, Parser, Subcommand};
 use indicatif::{ProgressBar, ProgressStyle};
-use itertools::Itertools;
-
-use crate::expansion::Expansion;
-use crate::filter::Filter;
-use crate::generate::GenerationBase;
-use crate::pipeline::{Pipeline, PipelineStep};
-use crate::symmetry::DihedralSubgroup;
-use crate::template::Template;
-
-mod bitmask;
-mod dfs_with_progress;
-mod expansion;
-mod fast_solver;
-mod filter;
-mod generate;
-mod logic;
-mod minlex;
-mod pipeline;
-mod sudoku;
-mod symmetry;
-mod template;
+
+use sudoku_utils::checkpoint::Checkpoint;
+use sudoku_utils::fast_solver::FastBruteForceSolver;
+use sudoku_utils::filter::Filter;
+use sudoku_utils::generate::GenerationBase;
+use sudoku_utils::logic::{cell_name, uniqueness_techniques, BasicSolver, Tier};
+use sudoku_utils::minlex::minlex;
+use sudoku_utils::pipeline::{Pipeline, PipelineFilter, RegionMaskedSudoku, RunOptions, Shard};
+use sudoku_utils::rating::rate;
+use sudoku_utils::recipe::{load_recipe, parse_filter, parse_step, parse_symmetry, parse_tier};
+use sudoku_utils::sudoku::Sudoku;
+use sudoku_utils::symmetry::DihedralSubgroup;
+use sudoku_utils::template::Template;
+
+/// Utilities for searching for, solving and analysing Sudoku puzzles.
+///
+/// Commands which take an input read one puzzle per line, from the given
+/// file or from stdin, using the first 81 characters of each line.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
+enum Command {
+    /// Search for puzzles fitting a template, passing them through a pipeline of steps
+    Search(SearchArgs),
+    /// Check that recipe files are valid, reporting any errors
+    Check { recipes: Vec<PathBuf> },
+    /// Print the solution of each puzzle
+    Solve { input: Option<PathBuf> },
+    /// Print the number of solutions of each puzzle
+    Count {
+        input: Option<PathBuf>,
+        /// Stop counting once this many solutions have been found
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Print the clues of each puzzle which could each be removed while keeping its solution unique
+    Redundant { input: Option<PathBuf> },
+    /// Print the minlex form of each puzzle
+    Minlex { input: Option<PathBuf> },
+    /// Print the Sudoku Explainer style rating of each puzzle, as ER/EP/ED
+    Rate {
+        input: Option<PathBuf>,
+        /// Instead print the number of cells that each puzzle leaves unsolved using basic techniques
+        #[arg(long)]
+        basics: bool,
+    },
+    /// Print each deduction made when solving each puzzle with logical techniques
+    Explain {
+        input: Option<PathBuf>,
+        /// The hardest tier of techniques to use, `basics`, `fish`, `wings`, `chains` or `als`
+        #[arg(long, default_value = "basics", value_parser = parse_tier)]
+        tier: Tier,
+        /// Also use the uniqueness techniques, assuming that each puzzle has a unique solution
+        #[arg(long)]
+        uniqueness: bool,
+    },
+    /// Print only those puzzles which pass all of the given filters
+    Filter {
+        input: Option<PathBuf>,
+        /// A filter to apply, e.g. `has_unique_solution` or `at_most_n_basic_placements n=0`
+        #[arg(long = "filter", value_name = "FILTER")]
+        filters: Vec<String>,
+    },
+}
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("symmetric_base").args(["grid", "random"])))]
+struct SearchArgs {
+    /// A recipe file describing the whole pipeline
+    #[arg(long, conflicts_with_all = ["template", "defines", "puzzles", "pattern", "grid", "random", "random_grids"], required_unless_present_any = ["template", "puzzles", "pattern", "grid", "random", "random_grids"])]
+    recipe: Option<PathBuf>,
+    /// The template, read left-to-right and top-to-bottom. Digits are givens,
+    /// bracketed digits such as `[123]` are wildcards, and anything else is empty.
+    #[arg(long)]
+    template: Option<String>,
+    /// Replace a placeholder character in the template, e.g. `X=[56789]`
+    #[arg(long = "define", value_name = "CHAR=TEXT")]
+    defines: Vec<String>,
+    /// Start from the puzzles in this file, one per line, instead of a template.
+    /// Use `-` to read them from stdin, which can't be checkpointed.
+    #[arg(long, conflicts_with_all = ["template", "defines"])]
+    puzzles: Option<PathBuf>,
+    /// Fill this pattern of clues, in which `.` and `0` are empty cells and anything
+    /// else is a clue, in every essentially different way giving a unique solution.
+    #[arg(long, conflicts_with_all = ["template", "defines", "puzzles"])]
+    pattern: Option<String>,
+    /// Search this solution grid for its puzzles with `--clues` clues
+    #[arg(long, conflicts_with_all = ["template", "defines", "puzzles", "pattern"], requires = "clues")]
+    grid: Option<String>,
+    /// The number of clues of the puzzles to find in `--grid`
+    #[arg(long, requires = "grid")]
+    clues: Option<usize>,
+    /// Make an endless stream of random minimal puzzles from this seed. The same
+    /// seed always makes the same puzzles, while `--threads` and `--shard` only
+    /// change which of them are made first.
+    #[arg(long, value_name = "SEED", conflicts_with_all = ["template", "defines", "puzzles", "pattern", "grid"])]
+    random: Option<u64>,
+    /// Make an endless stream of uniformly random solution grids from this seed,
+    /// which like `--random` depend on the seed alone.
+    #[arg(long, value_name = "SEED", conflicts_with_all = ["template", "defines", "puzzles", "pattern", "grid", "random"])]
+    random_grids: Option<u64>,
+    /// Only make puzzles from `--grid` or `--random` with this symmetry, e.g. `central`
+    #[arg(long, requires = "symmetric_base", value_parser = parse_symmetry, default_value = "trivial")]
+    symmetry: DihedralSubgroup,
+    /// A step to apply to the results, in order, after any steps from the recipe,
+    /// e.g. `expand plus_n n=2` or `filter has_unique_solution`
+    #[arg(long = "step", value_name = "STEP")]
+    steps: Vec<String>,
+    /// The number of worker threads to search with. Results from more than
+    /// one thread are not printed in a deterministic order.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    threads: u32,
+    /// Run only one of a number of shards of the search, e.g. `3/16`. The outputs
+    /// of all of the shards, in order, make up the output of the whole search
+    /// on a single thread. Sharding a search with a `non_equivalent` filter is
+    /// not supported and is an error, since each shard would only know about
+    /// the puzzles it has seen itself. Run the shards without it, then put their
+    /// combined outputs, in order, through `filter --filter non_equivalent` to
+    /// get the same output as the whole search.
+    #[arg(long, value_name = "INDEX/COUNT", value_parser = Shard::from_str)]
+    shard: Option<Shard>,
+    /// Save the position of the search to this file every so often, so that
+    /// the search can be continued later with `--resume`. Results printed after
+    /// the last checkpoint was saved are printed again when continuing.
+    #[arg(long, value_name = "FILE")]
+    checkpoint: Option<PathBuf>,
+    /// How often to save the checkpoint, in seconds
+    #[arg(long, value_name = "SECONDS", default_value_t = 60, requires = "checkpoint")]
+    checkpoint_interval: u64,
+    /// Continue the search from the checkpoint file, rather than starting afresh
+    #[arg(long, requires = "checkpoint")]
+    resume: bool,
+}
 
 fn main() {
+    match Cli::parse().command {
+        Command::Search(args) => search(args),
+        Command::Check { recipes } => {
+            let errors = recipes.iter().filter_map(|path| load_recipe(path).err()).inspect(|err| eprintln!("{}", err)).count();
+            if errors > 0 { exit(1); }
+        }
+        Command::Solve { input } => for_each_puzzle(input, |sudoku| {
+            match FastBruteForceSolver::unique_solution(&sudoku) {
+                Some(solution) => println!("{} {}", sudoku, solution),
+                None if FastBruteForceSolver::has_solution(&sudoku) => println!("{} multiple solutions", sudoku),
+                None => println!("{} no solution", sudoku),
+            }
+        }),
+        Command::Count { input, limit } => for_each_puzzle(input, |sudoku| {
+            let count = FastBruteForceSolver::count_solutions_with_limit(&sudoku, limit.unwrap_or(usize::MAX));
+            println!("{} {}", sudoku, count);
+        }),
+        Command::Redundant { input } => for_each_puzzle(input, |sudoku| {
+            match FastBruteForceSolver::count_solutions_with_limit(&sudoku, 2) {
+                0 => println!("{} no solution", sudoku),
+                1 => match FastBruteForceSolver::redundant_clues(&sudoku).as_slice() {
+                    [] => println!("{} minimal", sudoku),
+                    clues => println!("{} {}", sudoku, clues.iter().map(|&cell| cell_name(cell)).collect::<Vec<_>>().join(",")),
+                },
+                _ => println!("{} multiple solutions", sudoku),
+            }
+        }),
+        Command::Minlex { input } => for_each_puzzle(input, |sudoku| println!("{}", minlex(&sudoku))),
+        Command::Rate { input, basics: true } => for_each_puzzle(input, |sudoku| {
+            let mut solver = BasicSolver::for_region_masked_sudoku(&RegionMaskedSudoku::from_sudoku(&sudoku));
+            solver.solve_basics();
+            println!("{} {}", sudoku, solver.empty_cells());
+        }),
+        Command::Rate { input, basics: false } => for_each_puzzle(input, |sudoku| {
+            match rate(&RegionMaskedSudoku::from_sudoku(&sudoku)) {
+                Some(rating) => println!("{} {}", sudoku, rating),
+                None => println!("{} unrated", sudoku),
+            }
+        }),
+        Command::Explain { input, tier, uniqueness } => for_each_puzzle(input, |sudoku| {
+            let mut techniques = tier.techniques();
+            let mut solver = BasicSolver::for_region_masked_sudoku(&RegionMaskedSudoku::from_sudoku(&sudoku));
+            if uniqueness {
+                solver.assume_uniqueness();
+                techniques.extend(uniqueness_techniques());
+            }
+            solver.enable_log();
+            println!("{}", sudoku);
+            let outcome = loop {
+                let step = solver.step_with(&techniques);
+                solver.take_log().iter().for_each(|deduction| println!("  {}", deduction));
+                match step {
+                    Some(true) => {}
+                    Some(false) if solver.is_solved() => break "solved".to_string(),
+                    Some(false) => break format!("stuck with {} empty cells", solver.empty_cells()),
+                    None => break "contradiction".to_string(),
+                }
+            };
+            println!("  {}", outcome);
+        }),
+        Command::Filter { input, filters } => {
+            let filters: Vec<Filter> = filters.iter().map(|spec| parse_filter(spec).unwrap_or_else(|err| fail(&format!("filter `{}`: {}", spec, err)))).collect();
+            for_each_puzzle(input, |sudoku| {
+                let masked = RegionMaskedSudoku::from_sudoku(&sudoku);
+                if filters.iter().all(|filter| filter.matches(&masked)) {
+                    println!("{}", sudoku);
+                }
+            })
+        }
+    }
+}
+
+fn search(args: SearchArgs) {
+    let mut pipeline = match (args.recipe, args.template, args.puzzles, args.pattern) {
+        (Some(path), _, _, _) => load_recipe(&path).unwrap_or_else(|err| fail(&err)),
+        (None, _, _, _) if args.random_grids.is_some() => {
+            Pipeline { base: GenerationBase::RandomGrids { seed: args.random_grids.unwrap() }, steps: Vec::new() }
+        }
+        (None, _, _, _) if args.random.is_some() => {
+            Pipeline { base: GenerationBase::Random { seed: args.random.unwrap(), symmetry: args.symmetry }, steps: Vec::new() }
+        }
+        (None, _, _, _) if args.grid.is_some() => {
+            let grid = args.grid.as_deref().and_then(Sudoku::from_str).unwrap_or_else(|| fail("grid: expected a grid of 81 digits"));
+            let base = GenerationBase::solution_grid(grid, args.clues.unwrap(), args.symmetry).unwrap_or_else(|err| fail(&format!("grid: {}", err)));
+            Pipeline { base, steps: Vec::new() }
+        }
+        (None, _, _, Some(pattern)) => {
+            Pipeline { base: GenerationBase::pattern(&pattern).unwrap_or_else(|err| fail(&format!("pattern: {}", err))), steps: Vec::new() }
+        }
+        (None, _, Some(path), None) => {
+            let path = (path.as_os_str() != "-").then_some(path);
+            Pipeline { base: GenerationBase::puzzle_list(path).unwrap_or_else(|err| fail(&err)), steps: Vec::new() }
+        }
+        (None, Some(template), None, None) => {
+            let template = args.defines.iter().fold(template, |template, define| {
+                let (placeholder, replacement) = define.split_once('=').unwrap_or_else(|| fail(&format!("expected `CHAR=TEXT`, found `{}`", define)));
+                template.replace(placeholder, replacement)
+            });
+            let template = Template::from_str(&template).unwrap_or_else(|err| fail(&format!("template: {}", err)));
+            Pipeline { base: GenerationBase::Template(template), steps: Vec::new() }
+        }
+        (None, None, None, None) => unreachable!(),
+    };
+    pipeline.steps.extend(args.steps.iter().map(|spec| parse_step(spec).unwrap_or_else(|err| fail(&format!("step `{}`: {}", spec, err)))));
+
+    let resume_from = match &args.checkpoint {
+        Some(path) if args.resume => Some(Checkpoint::load(path).unwrap_or_else(|err| fail(&err))),
+        Some(path) if path.exists() => fail(&format!("{} already exists, use `--resume` to continue from it", path.display())),
+        _ => None,
+    };
+    let options = RunOptions {
+        threads: args.threads as usize,
+        shard: args.shard,
+        checkpoint: args.checkpoint,
+        checkpoint_interval: Duration::from_secs(args.checkpoint_interval),
+        resume_from,
+    };
+
     let bar = ProgressBar::new(100_000);
     bar.set_style(ProgressStyle::with_template("[{elapsed_precise}] {bar:50} {percent_precise}%")
         .unwrap()
         .progress_chars("#~."));
-    let template = Template::from_str(&"\
-        ..23.....\
-        .1..4....\
-        ...Y.....\
-        .76......\
-        8......B.\
-        9.....Y.B\
-        .....X..A\
-        .....X.A.\
-        ...XX....\
-    ".replace("A", "[12]").replace("B", "[34]").replace("X", "[56789]").replace("Y", "[123456789]"));
-    let pipeline = Pipeline {
-        base: GenerationBase::Template(template),
-        steps: vec![
-            PipelineStep::Expansion(Expansion::plus_n(4, DihedralSubgroup::DiagonalUrToDlSymm, "r1c1,r2c1,r3c1,r4c1,r7c1,r8c1,r9c1,r1c6,r2c6,r3c6,r4c6,r5c6,r6c6,r9c6,r4c4,r4c5,r4c7,r4c8,r4c9,r9c2,r9c3,r9c7,r9c8,r9c9")),
-            PipelineStep::Filter(Filter::HasUniqueSolution),
-            PipelineStep::Filter(Filter::at_most_n_basic_placements(0)),
-            PipelineStep::Filter(Filter::solves_with_basics_after_elims("56789r4c1,56789r4c6,56789r9c1,56789r9c6,1r7c3,1r8c3,2r7c2,2r8c2,3r5c5,3r6c5,4r5c4,4r6c4")),
-            PipelineStep::Filter(Filter::non_equivalent()),
-        ],
-    };
-    pipeline.into_iter(&bar).for_each(|sudoku| {
-        println!("{}", sudoku.digits().join(""));
-    });
+    pipeline.run(options, &bar, |sudoku| bar.suspend(|| println!("{}", sudoku))).unwrap_or_else(|err| fail(&err));
     bar.finish();
 }
+
+/// Run the given action on each puzzle read from the input, or from stdin
+/// if there is no input, skipping any lines which are not puzzles.
+fn for_each_puzzle<F: FnMut(Sudoku)>(input: Option<PathBuf>, mut action: F) {
+    let reader: Box<dyn BufRead> = match input {
+        Some(path) => Box::new(BufReader::new(File::open(&path).unwrap_or_else(|err| fail(&format!("{}: {}", path.display(), err))))),
+        None => Box::new(BufReader::new(io::stdin())),
+    };
+    for line in reader.lines() {
+        let line = line.unwrap_or_else(|err| fail(&err.to_string()));
+        match Sudoku::from_line(&line) {
+            Some(sudoku) => action(sudoku),
+            None => eprintln!("skipping line which is not a puzzle: {}", line),
+        }
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    exit(1)
+}
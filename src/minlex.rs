@@ -1,6 +1,6 @@
 use itertools::Itertools;
 
-use crate::{sudoku::Sudoku, symmetry::{DigitMapper, Symmetry}};
+use crate::{bitmask::Bitmask, sudoku::Sudoku, symmetry::{DigitMapper, Symmetry}};
 
 /// One of the 18 lines in a Sudoku.
 #[derive(Copy, Clone)]
@@ -11,6 +11,7 @@ enum Line {
 
 /// Apply a geometric symmetry plus relabelling which produces the
 /// minimal result, lexicographically speaking.
+#[allow(clippy::clone_on_copy)]
 pub fn minlex(sudoku: &Sudoku) -> Sudoku {
     
     // Start by determining, for each row and column, the digit count per minirow
@@ -94,6 +95,42 @@ pub fn minlex(sudoku: &Sudoku) -> Sudoku {
     Sudoku(result)
 }
 
+/// Every symmetry which maps the given pattern of cells onto itself, each given
+/// as the cell which is moved to each cell, in the same way as `minlex` morphs a
+/// puzzle. The identity is always among them.
+pub fn pattern_automorphisms(pattern: Bitmask<u128>) -> Vec<[u8; 81]> {
+    let mut automorphisms = Vec::new();
+    let row_counts = (0 .. 9).map(|r| (0 .. 9).filter(|c| pattern.contains(9 * r + c)).count()).collect::<Vec<_>>();
+    for transpose in [false, true] {
+        let preimage = |row_mapping: &[usize; 9], col_mapping: &[usize; 9], r: usize, c: usize| {
+            if transpose { 9 * col_mapping[c] + row_mapping[r] } else { 9 * row_mapping[r] + col_mapping[c] }
+        };
+        let line_counts = (0 .. 9).map(|line| {
+            (0 .. 9).filter(|&other| pattern.contains(if transpose { 9 * other + line } else { 9 * line + other })).count()
+        }).collect::<Vec<_>>();
+        for row_mapping in &WREATH_PERMS {
+            // Each row must be mapped from a line with as many cells of the pattern
+            if (0 .. 9).any(|r| line_counts[row_mapping[r]] != row_counts[r]) { continue; }
+            for col_mapping in &WREATH_PERMS {
+                if (0 .. 81).all(|cell| pattern.contains(cell) == pattern.contains(preimage(row_mapping, col_mapping, cell / 9, cell % 9))) {
+                    automorphisms.push(std::array::from_fn(|cell| preimage(row_mapping, col_mapping, cell / 9, cell % 9) as u8));
+                }
+            }
+        }
+    }
+    automorphisms
+}
+
+/// Check whether a puzzle is the least, lexicographically speaking, of all of the
+/// puzzles it can be morphed into using the given symmetries and relabelling.
+/// Symmetries are given as by `pattern_automorphisms`.
+pub fn is_least_under(sudoku: &Sudoku, symmetries: &[[u8; 81]]) -> bool {
+    symmetries.iter().all(|preimage| {
+        let mut mapper = DigitMapper::new();
+        !mapper.relabel(preimage.iter().map(|&cell| sudoku[cell as usize])).lt(sudoku.digits().copied())
+    })
+}
+
 /// The three digits in a given miniline.
 /// Index is either 0, 1 or 2 and describes whether this miniline is
 /// the first three, second three or last three digits in the line.
@@ -141,6 +178,7 @@ const THREE_PERMS: [[usize; 3]; 6] = [
 /// the minilines of a row/column, the indices of permutations in `THREE_PERMS`
 /// which, when applied to the bands in this line, order the minilines in
 /// increasing order of number of digits.
+#[allow(clippy::redundant_static_lifetimes)]
 const BAND_PERMS: [&'static [usize]; 64] = [
     &[0, 1, 2, 3, 4, 5], &[0, 2], &[0, 2], &[0, 2],
     &[1, 4], &[0, 1], &[0], &[0],
@@ -164,6 +202,7 @@ const BAND_PERMS: [&'static [usize]; 64] = [
 /// contain digits, the indices of permutations in `THREE_PERMS` which, when
 /// applied to the cells in this minirow, move the cells with digits all the
 /// way to the right.
+#[allow(clippy::redundant_static_lifetimes)]
 const COLUMN_PERMS: [&'static [usize]; 8] = [
     &[0, 1, 2, 3, 4, 5], &[0, 2], &[1, 4], &[0, 1], &[3, 5], &[2, 3], &[4, 5], &[0, 1, 2, 3, 4, 5],
 ];
@@ -195,6 +234,7 @@ const WREATH_PERM_RANGES: [[(usize, usize); 2]; 18] = [
 /// All possible permutations of three bands, and the lines within those bands.
 /// Ordered first lexicographically by band permutation, then by permutation of
 /// band 1, then by permutations of band 2, then by permutation of band 3.
+#[allow(clippy::large_const_arrays)]
 const WREATH_PERMS: [[usize; 9]; 1296] = [
     [0, 1, 2, 3, 4, 5, 6, 7, 8], [0, 1, 2, 3, 4, 5, 6, 8, 7], [0, 1, 2, 3, 4, 5, 7, 6, 8], [0, 1, 2, 3, 4, 5, 7, 8, 6], [0, 1, 2, 3, 4, 5, 8, 6, 7], [0, 1, 2, 3, 4, 5, 8, 7, 6], 
     [0, 1, 2, 3, 5, 4, 6, 7, 8], [0, 1, 2, 3, 5, 4, 6, 8, 7], [0, 1, 2, 3, 5, 4, 7, 6, 8], [0, 1, 2, 3, 5, 4, 7, 8, 6], [0, 1, 2, 3, 5, 4, 8, 6, 7], [0, 1, 2, 3, 5, 4, 8, 7, 6], 
@@ -413,3 +453,19 @@ const WREATH_PERMS: [[usize; 9]; 1296] = [
     [8, 7, 6, 5, 3, 4, 0, 1, 2], [8, 7, 6, 5, 3, 4, 0, 2, 1], [8, 7, 6, 5, 3, 4, 1, 0, 2], [8, 7, 6, 5, 3, 4, 1, 2, 0], [8, 7, 6, 5, 3, 4, 2, 0, 1], [8, 7, 6, 5, 3, 4, 2, 1, 0], 
     [8, 7, 6, 5, 4, 3, 0, 1, 2], [8, 7, 6, 5, 4, 3, 0, 2, 1], [8, 7, 6, 5, 4, 3, 1, 0, 2], [8, 7, 6, 5, 4, 3, 1, 2, 0], [8, 7, 6, 5, 4, 3, 2, 0, 1], [8, 7, 6, 5, 4, 3, 2, 1, 0], 
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn automorphisms_of_the_main_diagonal() {
+        // A symmetry keeps the main diagonal if it moves the columns in the same
+        // way as the rows, with or without transposing
+        let diagonal = Bitmask::<u128>::from_iter((0 .. 9).map(|idx| 10 * idx));
+        let automorphisms = pattern_automorphisms(diagonal);
+        assert_eq!(automorphisms.len(), 2 * 1296);
+        assert!(automorphisms.iter().all(|preimage| preimage.iter().enumerate().all(|(cell, &from)| diagonal.contains(cell) == diagonal.contains(from))));
+        assert!(automorphisms.contains(&std::array::from_fn(|cell| cell as u8)));
+    }
+}
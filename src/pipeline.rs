@@ -1,11 +1,20 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::fmt;
+use std::ops::Range;
+use std::path::PathBuf;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use indicatif::ProgressBar;
 
 use crate::bitmask::Bitmask;
-use crate::expansion::Expansion;
-use crate::filter::Filter;
+use crate::checkpoint::{Checkpoint, JobStatus};
+use crate::dfs_with_progress::{ResumableSearch, SearchPosition, Subtree};
 use crate::generate::GenerationBase;
 use crate::sudoku::Sudoku;
 
@@ -16,9 +25,47 @@ pub struct RegionMaskedSudoku {
     boxes: [Bitmask<u16>; 9],
 }
 
+/// A test that puzzles must pass to continue along a pipeline. This is implemented
+/// by `Filter`, and can be implemented elsewhere to add other filters to a pipeline.
+/// Filters are shared between the worker threads running a pipeline.
+pub trait PipelineFilter: Send + Sync {
+    fn matches(&self, sudoku: &RegionMaskedSudoku) -> bool;
+
+    /// The state built up by the filter over the puzzles it has seen so far,
+    /// as entries which can be given back to `restore_state` to resume. Each
+    /// entry is saved on a line of its own, so it can't contain a newline.
+    fn save_state(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Restore an entry of state previously returned by `save_state`
+    fn restore_state(&self, _entry: &str) -> Result<(), String> {
+        Err("this filter has no state".to_string())
+    }
+
+    /// Whether the filter's answer for a puzzle depends on the puzzles it has seen
+    /// before, so that it must see all of them for a run to give the right results
+    fn depends_on_earlier_puzzles(&self) -> bool {
+        false
+    }
+}
+
+/// A search for the puzzles which follow from each puzzle reaching it in a
+/// pipeline. This is implemented by `Expansion`, and can be implemented elsewhere
+/// to add other expansions to a pipeline.
+pub trait PipelineExpansion: Send + Sync {
+    fn expand(&self, sudoku: Rc<RefCell<RegionMaskedSudoku>>) -> PipelineSearch;
+
+    /// Resume expanding the given sudoku from a saved position, which a run resumed
+    /// from a checkpoint taken in the middle of the expansion needs
+    fn resume(&self, _sudoku: Rc<RefCell<RegionMaskedSudoku>>, _position: &SearchPosition) -> Result<PipelineSearch, String> {
+        Err("this expansion can't be resumed".to_string())
+    }
+}
+
 pub enum PipelineStep {
-    Filter(Filter),
-    Expansion(Expansion),
+    Filter(Box<dyn PipelineFilter>),
+    Expansion(Box<dyn PipelineExpansion>),
 }
 
 pub struct Pipeline {
@@ -26,29 +73,441 @@ pub struct Pipeline {
     pub steps: Vec<PipelineStep>,
 }
 
+pub type PipelineItem = (f64, f64, Rc<RefCell<RegionMaskedSudoku>>);
+
+/// A search producing the sudokus fed into a pipeline step, either from the
+/// base of the pipeline or from an expansion
+pub type PipelineSearch = Box<dyn ResumableSearch<Item = PipelineItem, Output = Rc<RefCell<RegionMaskedSudoku>>>>;
+
+/// How many jobs to split the search into for each worker thread, so that
+/// threads which finish their jobs early can pick up more work.
+const JOBS_PER_THREAD: usize = 64;
+
+/// How many jobs to split the search into for each shard when sharding. This
+/// doesn't depend on the number of threads, so that each shard can use as
+/// many threads as it likes while still agreeing on how the jobs are split.
+const JOBS_PER_SHARD: usize = 1024;
+
+/// How a pipeline should be run by `Pipeline::run`
+pub struct RunOptions {
+    pub threads: usize,
+    pub shard: Option<Shard>,
+    pub checkpoint: Option<PathBuf>,
+    pub checkpoint_interval: Duration,
+    pub resume_from: Option<Checkpoint>,
+}
+
+enum WorkerMessage {
+    Output(Sudoku),
+    Finished(usize),
+    Failed(String),
+}
+
+impl WorkerMessage {
+    fn handle<F: FnMut(Sudoku)>(self, statuses: &mut [JobStatus], action: &mut F) -> Result<(), String> {
+        match self {
+            Self::Output(sudoku) => action(sudoku),
+            Self::Finished(job) => statuses[job] = JobStatus::Done,
+            Self::Failed(err) => return Err(err),
+        }
+        Ok(())
+    }
+}
+
 impl Pipeline {
-    pub fn into_iter(self, bar: &ProgressBar) -> impl Iterator<Item = Sudoku> + '_ {
-        let mut base_iterator: Box<dyn Iterator<Item = (f64, f64, Rc<RefCell<RegionMaskedSudoku>>)>> = Box::new(self.base.iter().map(|(progress, scale, sudoku)| {
-            bar.set_position(((bar.length().unwrap() as f64) * progress).trunc() as u64);
-            (progress, scale, sudoku)
-        }));
-        for step in self.steps {
+
+    /// Run the pipeline on the current thread, reporting progress on the given bar
+    pub fn iter<'a>(&'a self, bar: &'a ProgressBar) -> Result<PipelineIter<'a>, String> {
+        let length = bar.length().unwrap() as f64;
+        Ok(PipelineIter::new(&self.steps, self.base.iter()?, Box::new(move |progress| bar.set_position((length * progress).trunc() as u64))))
+    }
+
+    /// Run the pipeline on the given number of worker threads, reporting progress
+    /// on the given bar. The base search is split into subtrees which the workers
+    /// take in turn, each running the whole chain of steps on its own subtrees.
+    /// The action is called on the current thread for each result. With a single
+    /// thread, results come in the same order as from `iter`.
+    ///
+    /// If a shard is given then only that shard's share of the jobs is run, and the
+    /// bar shows progress through those jobs alone. Shards take contiguous runs of
+    /// jobs, so the outputs of all of the shards in order are exactly the output of
+    /// the whole run on a single thread. A filter such as `non_equivalent`, which
+    /// depends on the puzzles seen before, would only know about those of its own
+    /// shard, so sharding a pipeline with one is an error. Instead the pipeline
+    /// can be sharded without it, and the outputs of the shards put through it.
+    ///
+    /// If a checkpoint file is given then the position of the run is saved to it
+    /// every so often, and once more at the end. A run can then be resumed from
+    /// the last checkpoint that was saved, and will carry on without repeating
+    /// any results that were produced before it.
+    pub fn run<F: FnMut(Sudoku)>(&self, options: RunOptions, bar: &ProgressBar, mut action: F) -> Result<(), String> {
+        if options.checkpoint.is_some() && matches!(self.base, GenerationBase::PuzzleList { path: None, .. }) {
+            return Err("puzzles read from stdin can't be checkpointed".to_string());
+        }
+        let order_dependent = self.steps.iter().position(|step| matches!(step, PipelineStep::Filter(filter) if filter.depends_on_earlier_puzzles()));
+        if let (Some(_), Some(step)) = (options.shard, order_dependent) {
+            return Err(format!("step {} depends on the puzzles seen before it, so it can't be sharded - filter the outputs of the shards with it instead", step + 1));
+        }
+        let split = match (&options.resume_from, options.shard) {
+            (Some(checkpoint), _) => checkpoint.split,
+            // Endless jobs never finish to make way for others, so there are only
+            // as many as there are threads to run them
+            (None, shard) if self.base.is_endless() => shard.map_or(1, |shard| shard.count) * options.threads,
+            (None, Some(shard)) => shard.count * JOBS_PER_SHARD,
+            (None, None) => options.threads * JOBS_PER_THREAD,
+        };
+        let jobs = self.base.subtrees(split);
+        let selected = options.shard.map(|shard| shard.job_range(jobs.len())).unwrap_or(0 .. jobs.len());
+        let mut statuses = match options.resume_from {
+            Some(checkpoint) if checkpoint.shard != options.shard => return Err(match checkpoint.shard {
+                Some(shard) => format!("the checkpoint was saved from shard {}", shard),
+                None => "the checkpoint was saved from a run which was not sharded".to_string(),
+            }),
+            Some(checkpoint) => self.restore(checkpoint, &jobs)?,
+            None => vec![JobStatus::Pending; jobs.len()],
+        };
+
+        // Jobs which were in progress are picked up again first
+        let running = selected.clone().filter_map(|job| match &statuses[job] {
+            JobStatus::Running(sources) => Some((job, Some(sources.clone()))),
+            _ => None,
+        });
+        let pending = selected.clone().filter(|&job| matches!(statuses[job], JobStatus::Pending)).map(|job| (job, None));
+        let queue = Mutex::new(running.chain(pending).collect::<VecDeque<_>>());
+
+        // The bar only shows progress through the selected jobs, so each of them
+        // is given the part of the bar that it accounts for among them
+        let length = bar.length().unwrap() as f64;
+        let total = selected.clone().map(|job| jobs[job].size).sum::<f64>().max(f64::MIN_POSITIVE);
+        let mut bar_ranges = vec![(0, 0); jobs.len()];
+        let mut offset = 0.0;
+        for job in selected.clone() {
+            let start = offset;
+            offset += jobs[job].size;
+            bar_ranges[job] = ((length * start / total).trunc() as u64, (length * offset / total).trunc() as u64);
+            if matches!(statuses[job], JobStatus::Done) { bar.inc(bar_ranges[job].1 - bar_ranges[job].0); }
+        }
+
+        let control = PauseControl::new(options.threads);
+        let (sender, receiver) = mpsc::channel();
+        thread::scope(|scope| {
+            for _ in 0 .. options.threads {
+                let (sender, jobs, bar_ranges, queue, control) = (sender.clone(), &jobs, &bar_ranges, &queue, &control);
+                scope.spawn(move || {
+                    let _guard = WorkerGuard(control);
+                    loop {
+                        let next_job = queue.lock().unwrap().pop_front();
+                        let Some((job, resume_from)) = next_job else { return; };
+
+                        // Each worker advances the shared bar by however much progress it has
+                        // made through its own job, and makes up the rest once it is done.
+                        let (subtree, (start, end)) = (&jobs[job], bar_ranges[job]);
+                        let reported = Cell::new(start);
+                        let report = Box::new(|progress: f64| {
+                            let fraction = if subtree.size > 0.0 { (progress - subtree.start) / subtree.size } else { 0.0 };
+                            let pos = (start + ((end - start) as f64 * fraction) as u64).clamp(reported.get(), end);
+                            bar.inc(pos - reported.replace(pos));
+                        });
+                        // `restore` has already checked that the jobs in progress can be resumed,
+                        // but the base can still fail to start, e.g. if a file has gone missing
+                        let iter = match resume_from {
+                            Some(sources) => PipelineIter::resume(&self.steps, &self.base, jobs[job].clone(), &sources, report),
+                            None => self.base.iter_subtree(jobs[job].clone()).map(|search| PipelineIter::new(&self.steps, search, report)),
+                        };
+                        let mut iter = match iter {
+                            Ok(iter) => iter,
+                            Err(err) => {
+                                control.stop();
+                                let _ = sender.send(WorkerMessage::Failed(err));
+                                return;
+                            }
+                        };
+
+                        while let Some(output) = iter.advance() {
+                            if let Some(sudoku) = output {
+                                if sender.send(WorkerMessage::Output(sudoku)).is_err() { return; }
+                            }
+                            if !control.pause_point(|| (job, iter.save_position())) { return; }
+                        }
+                        bar.inc(end - reported.get());
+                        if sender.send(WorkerMessage::Finished(job)).is_err() { return; }
+                    }
+                });
+            }
+            drop(sender);
+
+            let Some(path) = &options.checkpoint else {
+                return receiver.into_iter().try_for_each(|message| message.handle(&mut statuses, &mut action));
+            };
+
+            let mut last_saved = Instant::now();
+            loop {
+                match receiver.recv_timeout(options.checkpoint_interval.saturating_sub(last_saved.elapsed())) {
+                    Ok(message) => message.handle(&mut statuses, &mut action)?,
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+                if last_saved.elapsed() >= options.checkpoint_interval {
+                    // Once every worker has paused, deal with everything that they sent
+                    // beforehand so that the checkpoint matches what has been output.
+                    let paused = control.pause_all();
+                    let handled = receiver.try_iter().try_for_each(|message| message.handle(&mut statuses, &mut action));
+                    if handled.is_err() {
+                        control.resume_all(paused);
+                        return handled;
+                    }
+                    let mut checkpoint_statuses = statuses.clone();
+                    for (job, sources) in &paused.positions {
+                        checkpoint_statuses[*job] = JobStatus::Running(sources.clone());
+                    }
+                    let saved = self.checkpoint(split, options.shard, checkpoint_statuses).save(path);
+                    if saved.is_err() { control.stop(); }
+                    control.resume_all(paused);
+                    saved?;
+                    last_saved = Instant::now();
+                }
+            }
+            self.checkpoint(split, options.shard, statuses).save(path)
+        })
+    }
+
+    /// Restore the state of the filters from a checkpoint, and check that the
+    /// jobs in progress can be resumed, returning the status of each job
+    fn restore(&self, checkpoint: Checkpoint, jobs: &[Subtree]) -> Result<Vec<JobStatus>, String> {
+        let mismatch = || "the checkpoint was saved from a different search".to_string();
+        if checkpoint.jobs.len() != jobs.len() { return Err(mismatch()); }
+        for (step, entry) in &checkpoint.filter_states {
+            match self.steps.get(*step) {
+                Some(PipelineStep::Filter(filter)) => filter.restore_state(entry).map_err(|err| format!("step {}: {}", step + 1, err))?,
+                _ => return Err(mismatch()),
+            }
+        }
+        for (job, status) in jobs.iter().zip(&checkpoint.jobs) {
+            if let JobStatus::Running(sources) = status {
+                PipelineIter::resume(&self.steps, &self.base, job.clone(), sources, Box::new(|_| {}))?;
+            }
+        }
+        Ok(checkpoint.jobs)
+    }
+
+    fn checkpoint(&self, split: usize, shard: Option<Shard>, jobs: Vec<JobStatus>) -> Checkpoint {
+        let filter_states = self.steps.iter().enumerate().flat_map(|(idx, step)| match step {
+            PipelineStep::Filter(filter) => filter.save_state().into_iter().map(|entry| (idx, entry)).collect(),
+            PipelineStep::Expansion(_) => Vec::new(),
+        }).collect();
+        Checkpoint { split, shard, jobs, filter_states }
+    }
+}
+
+/// One of a number of shards which between them make up a whole run of a
+/// pipeline, numbered from 1
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Shard {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl Shard {
+
+    /// Parse a shard written as its index and the number of shards, e.g. `3/16`
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        let invalid = || format!("expected a shard such as `3/16`, found `{}`", s);
+        let (index, count) = s.split_once('/').ok_or_else(invalid)?;
+        let (index, count) = (index.trim().parse().map_err(|_| invalid())?, count.trim().parse().map_err(|_| invalid())?);
+        if index == 0 || index > count { return Err(format!("shard {} is out of range, shards are numbered from 1 to {}", index, count)); }
+        Ok(Self { index, count })
+    }
+
+    /// The jobs which belong to this shard, when there are the given number of jobs in all
+    fn job_range(&self, job_count: usize) -> Range<usize> {
+        (self.index - 1) * job_count / self.count .. self.index * job_count / self.count
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+/// Lets the thread running a pipeline pause all of its workers at a point between
+/// items, so that it can save a checkpoint which is consistent with the output.
+struct PauseControl {
+    requested: AtomicBool,
+    stopped: AtomicBool,
+    state: Mutex<PauseState>,
+    changed: Condvar,
+}
+
+struct PauseState {
+    running_workers: usize,
+    positions: Vec<(usize, Vec<SourcePosition>)>,
+    /// How many pauses have ended, so that a worker waiting for the end of one
+    /// pause isn't caught by the next one before it gets to run again
+    pauses_ended: usize,
+}
+
+impl PauseControl {
+    fn new(workers: usize) -> Self {
+        Self {
+            requested: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+            state: Mutex::new(PauseState { running_workers: workers, positions: Vec::new(), pauses_ended: 0 }),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// Called by a worker between items, which waits here if a pause has been
+    /// requested, recording the position it paused at. Returns false if the
+    /// worker should stop.
+    fn pause_point<P: FnOnce() -> (usize, Vec<SourcePosition>)>(&self, position: P) -> bool {
+        if self.requested.load(Ordering::Acquire) {
+            let mut state = self.state.lock().unwrap();
+            if self.requested.load(Ordering::Acquire) {
+                state.positions.push(position());
+                self.changed.notify_all();
+                let pause = state.pauses_ended;
+                while state.pauses_ended == pause {
+                    state = self.changed.wait(state).unwrap();
+                }
+            }
+        }
+        !self.stopped.load(Ordering::Acquire)
+    }
+
+    /// Wait until every running worker has paused
+    fn pause_all(&self) -> MutexGuard<'_, PauseState> {
+        self.requested.store(true, Ordering::Release);
+        let mut state = self.state.lock().unwrap();
+        while state.positions.len() < state.running_workers {
+            state = self.changed.wait(state).unwrap();
+        }
+        state
+    }
+
+    fn resume_all(&self, mut state: MutexGuard<'_, PauseState>) {
+        state.positions.clear();
+        state.pauses_ended += 1;
+        self.requested.store(false, Ordering::Release);
+        drop(state);
+        self.changed.notify_all();
+    }
+
+    fn stop(&self) {
+        self.stopped.store(true, Ordering::Release);
+    }
+}
+
+/// Marks a worker as no longer running when it finishes, even if it panics
+struct WorkerGuard<'a>(&'a PauseControl);
+
+impl Drop for WorkerGuard<'_> {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap_or_else(|err| err.into_inner()).running_workers -= 1;
+        self.0.changed.notify_all();
+    }
+}
+
+/// The position of one of the searches in progress in a `PipelineIter`
+#[derive(Clone, Debug)]
+pub struct SourcePosition {
+    pub next_step: usize,
+    pub progress_offset: f64,
+    pub progress_scale: f64,
+    pub search: SearchPosition,
+}
+
+/// An iterator over the results of a pipeline, which keeps a stack of the
+/// searches in progress: the base search at the bottom, and above it a search
+/// for each expansion being applied to a result from the search below.
+pub struct PipelineIter<'a> {
+    steps: &'a [PipelineStep],
+    sources: Vec<PipelineSource>,
+    report: Box<dyn FnMut(f64) + 'a>,
+}
+
+struct PipelineSource {
+    search: PipelineSearch,
+    next_step: usize,
+    progress_offset: f64,
+    progress_scale: f64,
+}
+
+impl<'a> PipelineIter<'a> {
+    fn new(steps: &'a [PipelineStep], base: PipelineSearch, report: Box<dyn FnMut(f64) + 'a>) -> Self {
+        let base = PipelineSource { search: base, next_step: 0, progress_offset: 0.0, progress_scale: 1.0 };
+        Self { steps, sources: vec![base], report }
+    }
+
+    /// Resume iterating over a subtree of the base search from a saved position
+    fn resume(steps: &'a [PipelineStep], base: &GenerationBase, subtree: Subtree, positions: &[SourcePosition], report: Box<dyn FnMut(f64) + 'a>) -> Result<Self, String> {
+        let mismatch = || "the checkpoint was saved from a different search".to_string();
+        let (base_position, expansion_positions) = positions.split_first().ok_or_else(mismatch)?;
+        let mut iter = Self::new(steps, base.resume_subtree(subtree, &base_position.search)?, report);
+        for position in expansion_positions {
+            let Some(PipelineStep::Expansion(expansion)) = position.next_step.checked_sub(1).and_then(|idx| steps.get(idx)) else {
+                return Err(mismatch());
+            };
+            let sudoku = iter.sources.last_mut().unwrap().search.current_output().ok_or_else(mismatch)?;
+            iter.sources.push(PipelineSource {
+                search: expansion.resume(sudoku, &position.search)?,
+                next_step: position.next_step,
+                progress_offset: position.progress_offset,
+                progress_scale: position.progress_scale,
+            });
+        }
+        Ok(iter)
+    }
+
+    /// The positions of all of the searches in progress, from the base upwards
+    pub fn save_position(&self) -> Vec<SourcePosition> {
+        self.sources.iter().map(|source| SourcePosition {
+            next_step: source.next_step,
+            progress_offset: source.progress_offset,
+            progress_scale: source.progress_scale,
+            search: source.search.save_position(),
+        }).collect()
+    }
+
+    /// Pass a single item from the innermost search in progress through the rest
+    /// of the pipeline, returning the result if it made it all of the way through.
+    /// Returns `None` once there are no more items.
+    pub fn advance(&mut self) -> Option<Option<Sudoku>> {
+        let source = self.sources.last_mut()?;
+        let Some((progress, scale, sudoku)) = source.search.next() else {
+            self.sources.pop();
+            return Some(None);
+        };
+        let progress = source.progress_offset + progress * source.progress_scale;
+        let scale = scale * source.progress_scale;
+        (self.report)(progress);
+
+        for (idx, step) in self.steps.iter().enumerate().skip(source.next_step) {
             match step {
-                PipelineStep::Filter(mut filter) => {
-                    base_iterator = Box::new(base_iterator.filter(move |(_, _, sudoku)| filter.matches(&sudoku.borrow())));
+                PipelineStep::Filter(filter) => {
+                    if !filter.matches(&sudoku.borrow()) { return Some(None); }
                 }
                 PipelineStep::Expansion(expansion) => {
-                    base_iterator = Box::new(base_iterator.flat_map(move |(progress, scale, sudoku)| 
-                        expansion.expand(sudoku).map(move |(subprogress, subscale, sudoku)| {
-                            let true_progress = progress - scale + subprogress * scale;
-                            bar.set_position(((bar.length().unwrap() as f64) * true_progress).trunc() as u64);
-                            (true_progress, scale * subscale, sudoku)
-                        })
-                    ))
+                    let search = expansion.expand(sudoku);
+                    self.sources.push(PipelineSource { search, next_step: idx + 1, progress_offset: progress - scale, progress_scale: scale });
+                    return Some(None);
                 }
             }
         }
-        base_iterator.map(|(_, _, sudoku)| sudoku.borrow().sudoku.clone())
+        let result = sudoku.borrow().sudoku.clone();
+        Some(Some(result))
+    }
+}
+
+impl Iterator for PipelineIter<'_> {
+    type Item = Sudoku;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(output) = self.advance() {
+            if output.is_some() { return output; }
+        }
+        None
     }
 }
 
@@ -64,6 +523,14 @@ impl RegionMaskedSudoku {
         }
     }
 
+    pub fn from_sudoku(sudoku: &Sudoku) -> Self {
+        let mut result = Self::empty();
+        for (idx, &digit) in sudoku.digits().enumerate() {
+            if digit != 0 { result.place(idx, digit); }
+        }
+        result
+    }
+
     #[inline(always)]
     pub fn sudoku(&self) -> &Sudoku {
         &self.sudoku
@@ -102,6 +569,7 @@ impl RegionMaskedSudoku {
     }
 }
 
+#[allow(clippy::unusual_byte_groupings)]
 pub const ALL_DIGITS: Bitmask<u16> = Bitmask::<u16>::from(0b_111_111_111_0);
 
 pub const ROW_INDICES: [usize; 81] = [
@@ -139,3 +607,197 @@ pub const BOX_INDICES: [usize; 81] = [
     6, 6, 6, 7, 7, 7, 8, 8, 8,
     6, 6, 6, 7, 7, 7, 8, 8, 8,
 ];
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use indicatif::ProgressDrawTarget;
+
+    use super::*;
+    use crate::dfs_with_progress::{DepthFirstSearcherWithProgress, DepthFirstTraversable};
+    use crate::recipe::parse_recipe;
+
+    const GRID: &str = "417369825632158947958724316825437169791586432346912758289643571573291684164875293";
+
+    /// A list of puzzles made by emptying different cells of a solution grid,
+    /// written to a file of its own for each test
+    fn puzzle_list(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("sudoku-utils-{}-{}.txt", name, std::process::id()));
+        let lines = (0 .. 30).map(|idx| {
+            GRID.chars().enumerate().map(|(cell, c)| if cell % 10 == idx % 10 || cell % 7 == idx % 7 { '.' } else { c }).collect::<String>()
+        });
+        fs::write(&path, lines.map(|line| line + "\n").collect::<String>()).unwrap();
+        path
+    }
+
+    /// A pipeline with an expansion and a filter which keeps state, so that a
+    /// checkpoint has to record both
+    fn pipeline(path: &Path) -> Pipeline {
+        let recipe = format!("base puzzles {}\nexpand minus_n n=1\nfilter has_unique_solution\nfilter non_equivalent\n", path.display());
+        parse_recipe(&recipe).unwrap()
+    }
+
+    /// A filter from outside of the crate, passing puzzles whose first cell is empty
+    struct FirstCellEmpty;
+
+    impl PipelineFilter for FirstCellEmpty {
+        fn matches(&self, sudoku: &RegionMaskedSudoku) -> bool {
+            sudoku.is_empty(0)
+        }
+    }
+
+    /// An expansion from outside of the crate, filling in each empty cell of a
+    /// puzzle in turn with its digit from `GRID`
+    struct FillOneCell;
+
+    impl PipelineExpansion for FillOneCell {
+        fn expand(&self, sudoku: Rc<RefCell<RegionMaskedSudoku>>) -> PipelineSearch {
+            Box::new(DepthFirstSearcherWithProgress::new(FillOneCellState { sudoku, filled: None }))
+        }
+    }
+
+    struct FillOneCellState {
+        sudoku: Rc<RefCell<RegionMaskedSudoku>>,
+        filled: Option<usize>,
+    }
+
+    impl DepthFirstTraversable for FillOneCellState {
+        type Step = usize;
+        type Output = Rc<RefCell<RegionMaskedSudoku>>;
+
+        fn next_steps(&mut self) -> Box<dyn ExactSizeIterator<Item = Self::Step>> {
+            if self.filled.is_some() { return Box::new(std::iter::empty()); }
+            let sudoku = self.sudoku.borrow();
+            Box::new((0 .. 81).filter(|&cell| sudoku.is_empty(cell)).collect::<Vec<_>>().into_iter())
+        }
+
+        fn apply_step(&mut self, &cell: &Self::Step) {
+            self.sudoku.borrow_mut().place(cell, GRID.as_bytes()[cell] - b'0');
+            self.filled = Some(cell);
+        }
+
+        fn revert_step(&mut self, &cell: &Self::Step) {
+            self.sudoku.borrow_mut().unplace(cell, GRID.as_bytes()[cell] - b'0');
+            self.filled = None;
+        }
+
+        fn should_prune(&mut self) -> bool {
+            false
+        }
+
+        fn output(&mut self) -> Option<Self::Output> {
+            self.filled.map(|_| self.sudoku.clone())
+        }
+    }
+
+    fn bar() -> ProgressBar {
+        ProgressBar::with_draw_target(Some(100_000), ProgressDrawTarget::hidden())
+    }
+
+    fn options(threads: usize) -> RunOptions {
+        RunOptions { threads, shard: None, checkpoint: None, checkpoint_interval: Duration::ZERO, resume_from: None }
+    }
+
+    fn run(pipeline: &Pipeline, options: RunOptions) -> Vec<Sudoku> {
+        let mut outputs = Vec::new();
+        pipeline.run(options, &bar(), |sudoku| outputs.push(sudoku)).unwrap();
+        outputs
+    }
+
+    #[test]
+    fn single_thread_run_matches_iter() {
+        let path = puzzle_list("order");
+        let expected = pipeline(&path).iter(&bar()).unwrap().collect::<Vec<_>>();
+        assert!(!expected.is_empty());
+        assert_eq!(run(&pipeline(&path), options(1)), expected);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn resumed_run_matches_uninterrupted_run() {
+        let path = puzzle_list("resume");
+        let checkpoint_path = path.with_extension("checkpoint");
+        let expected = run(&pipeline(&path), options(1));
+
+        // Save a checkpoint after every output, noting how many outputs each one
+        // came after. A checkpoint that changed since the previous output was saved
+        // after that output and before this one.
+        let mut outputs = Vec::new();
+        let mut checkpoints = Vec::<(usize, String)>::new();
+        let options_with_checkpoint = RunOptions { checkpoint: Some(checkpoint_path.clone()), ..options(1) };
+        pipeline(&path).run(options_with_checkpoint, &bar(), |sudoku| {
+            if let Ok(text) = fs::read_to_string(&checkpoint_path) {
+                if checkpoints.last().is_none_or(|(_, last)| *last != text) { checkpoints.push((outputs.len(), text)); }
+            }
+            outputs.push(sudoku);
+        }).unwrap();
+        assert_eq!(outputs, expected);
+        assert!(checkpoints.len() > 3);
+
+        for (done, text) in [&checkpoints[1], &checkpoints[checkpoints.len() / 2], &checkpoints[checkpoints.len() - 1]] {
+            let resume_from = Some(Checkpoint::from_text(text).unwrap());
+            let resumed = run(&pipeline(&path), RunOptions { resume_from, ..options(1) });
+            assert_eq!([&expected[.. *done], &resumed].concat(), expected);
+        }
+        fs::remove_file(path).unwrap();
+        fs::remove_file(checkpoint_path).unwrap();
+    }
+
+    #[test]
+    fn shards_make_up_the_whole_run() {
+        let path = puzzle_list("shards");
+        let recipe = format!("base puzzles {}\nexpand minus_n n=1\nfilter has_unique_solution\n", path.display());
+        let expected = parse_recipe(&recipe).unwrap().iter(&bar()).unwrap().collect::<Vec<_>>();
+        for count in [1, 3, 7] {
+            let outputs = (1 ..= count).flat_map(|index| {
+                let shard = Some(Shard { index, count });
+                run(&parse_recipe(&recipe).unwrap(), RunOptions { shard, ..options(1) })
+            }).collect::<Vec<_>>();
+            assert_eq!(outputs, expected);
+        }
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn sharding_a_non_equivalent_filter_is_an_error() {
+        let recipe = "base puzzles -\nfilter has_unique_solution\nfilter any (at_most_n_basic_placements n=0) (not (non_equivalent))\n";
+        let shard = Some(Shard { index: 1, count: 2 });
+        assert!(parse_recipe(recipe).unwrap().run(RunOptions { shard, ..options(1) }, &bar(), |_| {}).is_err());
+    }
+
+    #[test]
+    fn steps_from_outside_of_the_crate() {
+        let path = puzzle_list("custom");
+        let pipeline = Pipeline {
+            base: GenerationBase::puzzle_list(Some(path.clone())).unwrap(),
+            steps: vec![PipelineStep::Expansion(Box::new(FillOneCell)), PipelineStep::Filter(Box::new(FirstCellEmpty))],
+        };
+
+        let puzzles = fs::read_to_string(&path).unwrap().lines().map(|line| Sudoku::from_str(line).unwrap()).collect::<Vec<_>>();
+        let expected = puzzles.iter().filter(|puzzle| puzzle[0] == 0).flat_map(|puzzle| {
+            (1 .. 81).filter(|&cell| puzzle[cell] == 0).map(|cell| {
+                let mut filled = puzzle.clone();
+                filled[cell] = GRID.as_bytes()[cell] - b'0';
+                filled
+            })
+        }).collect::<Vec<_>>();
+        assert!(!expected.is_empty());
+        assert_eq!(pipeline.iter(&bar()).unwrap().collect::<Vec<_>>(), expected);
+        assert_eq!(run(&pipeline, options(1)), expected);
+
+        let sudoku = Rc::new(RefCell::new(RegionMaskedSudoku::from_sudoku(&puzzles[0])));
+        let position = SearchPosition { started: true, progress: 0.0, levels: vec![1] };
+        assert!(FillOneCell.resume(sudoku, &position).is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn worker_errors_are_returned() {
+        let path = puzzle_list("missing");
+        let pipeline = pipeline(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(pipeline.run(options(2), &bar(), |_| {}).is_err());
+    }
+}
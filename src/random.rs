@@ -0,0 +1,374 @@
+use crate::bitmask::Bitmask;
+use crate::pipeline::ALL_DIGITS;
+use crate::sudoku::Sudoku;
+
+/// A small, fast pseudorandom number generator (xoshiro256**). The sequence of
+/// numbers for each seed is fixed, so anything generated from a seed can be
+/// generated again from the same seed.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+
+    /// A generator seeded with a single number
+    pub fn new(seed: u64) -> Self {
+        Self::from_seeds(&[seed])
+    }
+
+    /// A generator seeded with several numbers, such as a seed and the index of
+    /// an item, so that each item has a separate stream of its own
+    pub fn from_seeds(seeds: &[u64]) -> Self {
+        // Mix the seeds in with SplitMix64, which spreads out even similar seeds
+        let mut mix = 0x853c_49e6_748f_ea9b_u64;
+        for &seed in seeds {
+            let mut input = mix ^ seed;
+            mix = split_mix(&mut input);
+        }
+        let mut state = [0; 4];
+        for word in &mut state { *word = split_mix(&mut mix); }
+        Self { state }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let result = self.state[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.state[1] << 17;
+        self.state[2] ^= self.state[0];
+        self.state[3] ^= self.state[1];
+        self.state[1] ^= self.state[2];
+        self.state[0] ^= self.state[3];
+        self.state[2] ^= t;
+        self.state[3] = self.state[3].rotate_left(45);
+        result
+    }
+
+    /// A number from 0 up to but not including `n`, with every number equally likely
+    pub fn below(&mut self, n: usize) -> usize {
+        assert!(n > 0, "can't choose from an empty range");
+        // Reject the numbers at the top which would make the lower ones more likely
+        let n = n as u64;
+        let limit = u64::MAX - u64::MAX % n;
+        loop {
+            let x = self.next_u64();
+            if x < limit { return (x % n) as usize; }
+        }
+    }
+
+    /// Put the items into a random order, with every order equally likely
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for idx in (1 .. items.len()).rev() {
+            items.swap(idx, self.below(idx + 1));
+        }
+    }
+}
+
+fn split_mix(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// The most ways there are to fill a band given the three digits in each of its
+/// columns, found by trying every way of splitting the digits of each box between
+/// its columns
+const MAX_BAND_FILLINGS: usize = 1728;
+
+/// A solution grid chosen uniformly at random from all of the solution grids.
+///
+/// A band is made up of the three digits in each of its columns, together with
+/// one of the ways of filling the band with them. The first band is chosen
+/// uniformly, and then the digits in each column of the second band, out of the
+/// 56 ways for each stack that avoid the digits of the first band. This fixes
+/// the digits in each column of the third band. Grids with these columns are
+/// as common as the product of the numbers of ways of filling the second and
+/// third bands, so the choice is kept with probability in proportion to this
+/// product, and otherwise everything is chosen again, about 80 times on average.
+/// The second and third bands are then filled in one of their ways, chosen uniformly.
+pub fn uniform_solution_grid(rng: &mut Rng) -> Sudoku {
+    loop {
+        let band = random_band(rng);
+        let columns = std::array::from_fn(|col| Bitmask::<u16>::from_iter((0 .. 3).map(|row| band[9 * row + col] as usize)));
+        let Some((second, third)) = random_lower_bands(&columns, rng) else { continue; };
+        let mut sudoku = Sudoku::empty();
+        for (idx, &digit) in band.iter().chain(&second).chain(&third).enumerate() {
+            sudoku[idx] = digit;
+        }
+        return sudoku;
+    }
+}
+
+/// A band chosen uniformly at random from all of the ways of filling a band. Each
+/// band has a single first row, and one of the 56 ways of splitting the digits
+/// between the boxes of the second row so that they avoid the boxes' digits in
+/// the first, after which the order of the digits in each row of a box is free.
+fn random_band(rng: &mut Rng) -> [u8; 27] {
+    let mut digits = (1 ..= 9).collect::<Vec<u8>>();
+    rng.shuffle(&mut digits);
+    let first_row: [Bitmask<u16>; 3] = std::array::from_fn(|bx| Bitmask::<u16>::from_iter(digits[3 * bx .. 3 * bx + 3].iter().map(|&digit| digit as usize)));
+    let second_row = random_disjoint_partition(first_row, rng);
+    let third_row = std::array::from_fn(|bx| ALL_DIGITS & !(first_row[bx] | second_row[bx]));
+
+    let mut band = [0; 27];
+    band[.. 9].copy_from_slice(&digits);
+    for (row, minirows) in [(1, second_row), (2, third_row)] {
+        for (bx, minirow) in minirows.iter().enumerate() {
+            let mut digits = minirow.as_bit_iter().map(|digit| digit as u8).collect::<Vec<_>>();
+            rng.shuffle(&mut digits);
+            band[9 * row + 3 * bx .. 9 * row + 3 * bx + 3].copy_from_slice(&digits);
+        }
+    }
+    band
+}
+
+/// Choose the digits in each column of the second band, and if they are kept,
+/// the fillings of the second and third bands
+fn random_lower_bands(columns: &[Bitmask<u16>; 9], rng: &mut Rng) -> Option<([u8; 27], [u8; 27])> {
+    let mut second_columns = [Bitmask::<u16>::empty(); 9];
+    for stack in 0 .. 3 {
+        let partition = random_disjoint_partition([columns[3 * stack], columns[3 * stack + 1], columns[3 * stack + 2]], rng);
+        second_columns[3 * stack .. 3 * stack + 3].copy_from_slice(&partition);
+    }
+    // Each digit is in a different column of a stack in the first two bands, so the
+    // third band has it in the column left over
+    let third_columns = std::array::from_fn(|col| ALL_DIGITS & !(columns[col] | second_columns[col]));
+
+    // Keeping each band in proportion to its number of fillings in turn keeps both
+    // in proportion to the product, and saves counting the third band's fillings
+    // if the second is thrown away
+    if rng.below(MAX_BAND_FILLINGS) >= count_band_fillings(&second_columns) { return None; }
+    if rng.below(MAX_BAND_FILLINGS) >= count_band_fillings(&third_columns) { return None; }
+    let second = nth_band_filling(&second_columns, rng.below(count_band_fillings(&second_columns)));
+    let third = nth_band_filling(&third_columns, rng.below(count_band_fillings(&third_columns)));
+    Some((second, third))
+}
+
+/// Given three sets of three digits which between them hold every digit, choose
+/// uniformly from the 56 ways of splitting the digits into three more such sets,
+/// each having none of the digits of the given set in the same place. Each digit
+/// moves to one of the two other places, and if `k` of the digits of the first set
+/// move to the second place, then `3 - k` of those of the second set move to the
+/// first place and `k` of those of the third set move to the first place.
+fn random_disjoint_partition(sets: [Bitmask<u16>; 3], rng: &mut Rng) -> [Bitmask<u16>; 3] {
+    // There are C(3, k)^3 ways for each `k`
+    let k = match rng.below(56) {
+        0 => 0,
+        1 ..= 27 => 1,
+        28 ..= 54 => 2,
+        _ => 3,
+    };
+    let (first_to_second, first_to_third) = random_split(sets[0], k, rng);
+    let (second_to_first, second_to_third) = random_split(sets[1], 3 - k, rng);
+    let (third_to_first, third_to_second) = random_split(sets[2], k, rng);
+    [second_to_first | third_to_first, first_to_second | third_to_second, first_to_third | second_to_third]
+}
+
+/// Split a set of three digits into a random subset of the given size and the rest
+fn random_split(digits: Bitmask<u16>, size: usize, rng: &mut Rng) -> (Bitmask<u16>, Bitmask<u16>) {
+    let mut bits = digits.as_bit_iter();
+    let mut digits: [usize; 3] = std::array::from_fn(|_| bits.next().unwrap());
+    rng.shuffle(&mut digits);
+    (Bitmask::<u16>::from_iter(digits[.. size].iter().copied()), Bitmask::<u16>::from_iter(digits[size ..].iter().copied()))
+}
+
+/// The number of ways of filling a band with the given digits in each column. The
+/// digits in a row of a box, one from each column, are chosen for the first row
+/// and then the second, each time making sure that the rows of the boxes have
+/// different digits. The third row then has the digits left in each column.
+fn count_band_fillings(columns: &[Bitmask<u16>; 9]) -> usize {
+    let mut count = 0;
+    for_each_band_row(columns, |rows| {
+        let left = std::array::from_fn(|col| columns[col] & !rows[col / 3]);
+        count += 1 << cycle_count(&left);
+    });
+    count
+}
+
+/// With two digits left in each column and each digit left in two columns, linking
+/// the columns which share a digit splits them into cycles. The second row can take
+/// either digit from any one column of a cycle, which then fixes the rest of it.
+fn cycle_count(columns: &[Bitmask<u16>; 9]) -> usize {
+    let mut unvisited = Bitmask::<u16>::from_iter(0 .. 9);
+    let mut cycles = 0;
+    while let Some(start) = unvisited.as_bit_iter().next() {
+        cycles += 1;
+        unvisited.unset(start);
+        let mut digits = columns[start];
+        while let Some(col) = unvisited.as_bit_iter().find(|&col| (columns[col] & digits).is_not_empty()) {
+            unvisited.unset(col);
+            digits |= columns[col];
+        }
+    }
+    cycles
+}
+
+/// Call the function with each way of choosing one digit from each column for a
+/// row of the band, given as the digits in the row of each box
+fn for_each_band_row<F: FnMut([Bitmask<u16>; 3])>(columns: &[Bitmask<u16>; 9], mut f: F) {
+    let (first_choices, second_choices) = (box_row_choices(columns, 0), box_row_choices(columns, 1));
+    for &first in &first_choices.0[.. first_choices.1] {
+        for &second in &second_choices.0[.. second_choices.1] {
+            if (first & second).is_not_empty() { continue; }
+            // The third box takes the digits left over, one from each of its columns
+            let third = ALL_DIGITS & !(first | second);
+            if (6 .. 9).all(|col| (third & columns[col]).count_ones() == 1) { f([first, second, third]); }
+        }
+    }
+}
+
+/// The ways of choosing one digit from each column of a box for a row, and how many there are
+fn box_row_choices(columns: &[Bitmask<u16>; 9], bx: usize) -> ([Bitmask<u16>; 27], usize) {
+    let (mut choices, mut count) = ([Bitmask::<u16>::empty(); 27], 0);
+    for x in columns[3 * bx].as_bit_iter() {
+        for y in columns[3 * bx + 1].as_bit_iter() {
+            for z in columns[3 * bx + 2].as_bit_iter() {
+                choices[count] = Bitmask::<u16>::from_iter([x, y, z].into_iter());
+                count += 1;
+            }
+        }
+    }
+    (choices, count)
+}
+
+/// One of the ways of filling a band with the given digits in each column, in the
+/// order in which `count_band_fillings` counts them
+fn nth_band_filling(columns: &[Bitmask<u16>; 9], mut n: usize) -> [u8; 27] {
+    let mut rows = None;
+    for_each_band_row(columns, |first| {
+        let left = std::array::from_fn(|col| columns[col] & !first[col / 3]);
+        for_each_band_row(&left, |second| {
+            if n == 0 { rows = Some((first, second)); }
+            n = n.wrapping_sub(1);
+        });
+    });
+    let (first, second) = rows.expect("there are not that many ways of filling the band");
+    // The rows of the boxes have one digit from each column
+    std::array::from_fn(|idx| {
+        let (row, col) = (idx / 9, idx % 9);
+        let digits = match row {
+            0 => columns[col] & first[col / 3],
+            1 => columns[col] & second[col / 3],
+            _ => columns[col] & !first[col / 3] & !second[col / 3],
+        };
+        digits.as_bit_iter().next().unwrap() as u8
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use itertools::Itertools;
+
+    use super::*;
+
+
+    /// Every way of splitting the digits into three sets of three, ignoring their order
+    fn partitions() -> Vec<[Bitmask<u16>; 3]> {
+        let all = Bitmask::<u16>::from_iter(1 ..= 9);
+        let mut partitions = Vec::new();
+        for first in all.as_subset_iter().filter(|set| set.count_ones() == 3 && set.contains(1)) {
+            let rest = ALL_DIGITS & !first;
+            let least = rest.as_bit_iter().next().unwrap();
+            for second in rest.as_subset_iter().filter(|set| set.count_ones() == 3 && set.contains(least)) {
+                partitions.push([first, second, rest & !second]);
+            }
+        }
+        partitions
+    }
+
+    /// The digits in each column of the given band
+    fn band_columns(band: &[u8]) -> [Bitmask<u16>; 9] {
+        std::array::from_fn(|col| Bitmask::<u16>::from_iter((0 .. 3).map(|row| band[9 * row + col] as usize)))
+    }
+
+    /// The number of ways of filling a band with the given digits in each column,
+    /// counted by trying every order of the digits in each column. The orders for
+    /// the first two stacks fix the digits left for each row of the third stack.
+    fn count_band_fillings_naively(columns: &[Bitmask<u16>; 9]) -> usize {
+        let stack_rows = |stack: usize| {
+            let orders = (0 .. 3).map(|col| columns[3 * stack + col].as_bit_iter().permutations(3).collect::<Vec<_>>()).collect::<Vec<_>>();
+            orders[0].iter().flat_map(|x| orders[1].iter().flat_map(|y| orders[2].iter().map(|z| {
+                std::array::from_fn::<_, 3, _>(|row| Bitmask::<u16>::from_iter([x[row], y[row], z[row]].into_iter()))
+            }))).collect::<Vec<_>>()
+        };
+        let third = stack_rows(2);
+        let mut count = 0;
+        for first in stack_rows(0) {
+            for second in stack_rows(1) {
+                if (0 .. 3).any(|row| (first[row] & second[row]).is_not_empty()) { continue; }
+                let left: [_; 3] = std::array::from_fn(|row| ALL_DIGITS & !(first[row] | second[row]));
+                count += third.iter().filter(|&&rows| rows == left).count();
+            }
+        }
+        count
+    }
+
+    fn is_solution_grid(sudoku: &Sudoku) -> bool {
+        (0 .. 9).all(|unit| {
+            let row = Bitmask::<u16>::from_iter((0 .. 9).map(|idx| sudoku[9 * unit + idx] as usize));
+            let col = Bitmask::<u16>::from_iter((0 .. 9).map(|idx| sudoku[9 * idx + unit] as usize));
+            let bx = Bitmask::<u16>::from_iter((0 .. 9).map(|idx| sudoku[27 * (unit / 3) + 3 * (unit % 3) + 9 * (idx / 3) + idx % 3] as usize));
+            row == ALL_DIGITS && col == ALL_DIGITS && bx == ALL_DIGITS
+        })
+    }
+
+    #[test]
+    fn seeds_give_fixed_sequences() {
+        let mut rng = Rng::from_seeds(&[1, 2, 3]);
+        assert_eq!([rng.next_u64(), rng.next_u64(), rng.next_u64()], [0xf889_07f3_39b7_e35d, 0x46f6_0641_47bd_3402, 0x92bf_e0b9_c71f_2580]);
+        let mut rng = Rng::new(42);
+        assert_eq!(uniform_solution_grid(&mut rng).to_string(), "317452689528697413649318275475231896163789524982546137296174358751863942834925761");
+    }
+
+    #[test]
+    fn sampled_grids_are_solution_grids() {
+        let mut rng = Rng::new(1);
+        for _ in 0 .. 50 {
+            assert!(is_solution_grid(&uniform_solution_grid(&mut rng)));
+        }
+    }
+
+    #[test]
+    fn band_fillings_are_counted_and_enumerated() {
+        let mut rng = Rng::new(2);
+        for _ in 0 .. 4 {
+            let sudoku = uniform_solution_grid(&mut rng);
+            for band in sudoku.0.chunks(27) {
+                let columns = band_columns(band);
+                let count = count_band_fillings(&columns);
+                assert!(count <= MAX_BAND_FILLINGS);
+                assert_eq!(count, count_band_fillings_naively(&columns));
+
+                // Each filling is different, and has the same digits in each column
+                let fillings = (0 .. count).map(|n| nth_band_filling(&columns, n)).collect::<HashSet<_>>();
+                assert_eq!(fillings.len(), count);
+                assert!(fillings.contains(band));
+                for filling in &fillings {
+                    assert!(band_columns(filling) == columns);
+                    assert!((0 .. 3).all(|row| Bitmask::<u16>::from_iter(filling[9 * row .. 9 * row + 9].iter().map(|&digit| digit as usize)) == ALL_DIGITS));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn max_band_fillings() {
+        // Relabelling the digits can make the first stack's columns any partition,
+        // and reordering the columns within a stack, or the last two stacks,
+        // doesn't change the count
+        let first = [Bitmask::<u16>::from_iter(1 ..= 3), Bitmask::<u16>::from_iter(4 ..= 6), Bitmask::<u16>::from_iter(7 ..= 9)];
+        let partitions = partitions();
+        assert_eq!(partitions.len(), 280);
+        let mut max = 0;
+        for (idx, second) in partitions.iter().enumerate() {
+            for third in &partitions[idx ..] {
+                let columns = std::array::from_fn(|col| [first, *second, *third][col / 3][col % 3]);
+                max = max.max(count_band_fillings(&columns));
+            }
+        }
+        assert_eq!(max, MAX_BAND_FILLINGS);
+    }
+}
@@ -0,0 +1,174 @@
+use std::fmt;
+
+use crate::fast_solver::FastBruteForceSolver;
+use crate::logic::{uniqueness_techniques, BasicSolver, Deduction, Technique, Tier};
+use crate::pipeline::RegionMaskedSudoku;
+
+/// A rating of how hard a puzzle is to solve logically, on a scale like that of
+/// Sudoku Explainer: the difficulty of the hardest step needed to solve it (ER),
+/// of the hardest step up to the first placement (EP), and of the first step (ED)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Rating {
+    pub er: f64,
+    pub ep: f64,
+    pub ed: f64,
+}
+
+/// Written as `ER/EP/ED`, for example `7.2/1.5/1.2`
+impl fmt::Display for Rating {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.1}/{:.1}/{:.1}", self.er, self.ep, self.ed)
+    }
+}
+
+/// Rate a puzzle by solving it one step at a time, always taking the easiest
+/// deduction available. The uniqueness techniques are only used if the puzzle
+/// has a unique solution. Puzzles without a solution, or which the solver
+/// gets stuck on, are unrated.
+pub fn rate(sudoku: &RegionMaskedSudoku) -> Option<Rating> {
+    let solution_count = FastBruteForceSolver::count_solutions_with_limit(sudoku.sudoku(), 2);
+    if solution_count == 0 { return None; }
+
+    let mut solver = BasicSolver::for_region_masked_sudoku(sudoku);
+    let mut techniques = vec![
+        Technique::HiddenSingle, Technique::NakedSingle, Technique::Pointing, Technique::Claiming,
+        Technique::NakedSubset(2), Technique::HiddenSubset(2), Technique::NakedSubset(3),
+        Technique::HiddenSubset(3), Technique::NakedSubset(4), Technique::HiddenSubset(4),
+    ];
+    techniques.extend(Tier::Als.techniques());
+    if solution_count == 1 {
+        solver.assume_uniqueness();
+        techniques.extend(uniqueness_techniques());
+    }
+    techniques.sort_by(|a, b| base_difficulty(*a).total_cmp(&base_difficulty(*b)));
+
+    let mut rating: Option<Rating> = None;
+    let mut placed = false;
+    while !solver.is_solved() {
+        let (deduction, step) = easiest_deduction(&solver, &techniques)?;
+        let rating = rating.get_or_insert(Rating { er: step, ep: step, ed: step });
+        rating.er = rating.er.max(step);
+        if !placed { rating.ep = rating.ep.max(step); }
+        placed |= !deduction.placements.is_empty();
+        solver.apply(deduction);
+    }
+    Some(rating.unwrap_or(Rating { er: 0.0, ep: 0.0, ed: 0.0 }))
+}
+
+/// Find the easiest deduction that the given techniques, sorted by their base
+/// difficulty, can make, along with its difficulty
+fn easiest_deduction(solver: &BasicSolver, techniques: &[Technique]) -> Option<(Deduction, f64)> {
+    let mut best: Option<(Deduction, f64)> = None;
+    for &technique in techniques {
+        // Nothing after this can be any easier than what has been found
+        if best.as_ref().is_some_and(|(_, step)| base_difficulty(technique) >= *step) { break; }
+        if let Some(deduction) = solver.find(technique) {
+            let step = difficulty(&deduction);
+            if best.as_ref().is_none_or(|(_, best_step)| step < *best_step) {
+                best = Some((deduction, step));
+            }
+        }
+    }
+    best
+}
+
+/// The least difficulty of any deduction made using a technique
+pub fn base_difficulty(technique: Technique) -> f64 {
+    match technique {
+        Technique::HiddenSingle => 1.2,
+        Technique::NakedSingle => 2.3,
+        Technique::Pointing => 2.6,
+        Technique::Claiming => 2.8,
+        Technique::NakedSubset(2) => 3.0,
+        Technique::Fish(2) => 3.2,
+        Technique::HiddenSubset(2) | Technique::FinnedFish(2) => 3.4,
+        Technique::SashimiFish(2) => 3.5,
+        Technique::NakedSubset(3) => 3.6,
+        Technique::Fish(3) => 3.8,
+        Technique::HiddenSubset(3) | Technique::Skyscraper | Technique::FinnedFish(3) => 4.0,
+        Technique::TwoStringKite | Technique::SashimiFish(3) => 4.1,
+        Technique::XyWing | Technique::EmptyRectangle => 4.2,
+        Technique::XyzWing | Technique::WWing => 4.4,
+        Technique::UniqueRectangle(1) | Technique::AvoidableRectangle(1) => 4.5,
+        Technique::UniqueRectangle(_) | Technique::AvoidableRectangle(_) => 4.6,
+        Technique::SimpleColoring => 4.8,
+        Technique::NakedSubset(4) => 5.0,
+        Technique::Fish(4) => 5.2,
+        Technique::HiddenSubset(4) | Technique::FinnedFish(4) => 5.4,
+        Technique::SashimiFish(4) => 5.5,
+        Technique::BugPlusOne => 5.6,
+        Technique::NakedSubset(_) | Technique::HiddenSubset(_) | Technique::Fish(_)
+            | Technique::FinnedFish(_) | Technique::SashimiFish(_) => 6.0,
+        Technique::XChain(_) => 6.5,
+        Technique::XyChain(_) => 6.6,
+        Technique::Aic(_) => 7.0,
+        Technique::AlsXz => 7.5,
+        Technique::AlsXyWing => 7.8,
+        Technique::DeathBlossom => 8.0,
+    }
+}
+
+/// The difficulty of a single deduction, which for some techniques depends on
+/// the size of the pattern that was found
+fn difficulty(deduction: &Deduction) -> f64 {
+    let base = base_difficulty(deduction.technique);
+    match deduction.technique {
+        // Hidden Singles are easier to spot in a box than in a line
+        Technique::HiddenSingle if deduction.regions.iter().any(|&region| region < 18) => 1.5,
+        // The roof of the rectangle forms a subset with the extra cells
+        Technique::UniqueRectangle(3) => base + 0.1 * (deduction.cells.len() - 5) as f64,
+        Technique::XChain(length) | Technique::XyChain(length) | Technique::Aic(length) => base + length_difficulty(length),
+        _ => base,
+    }
+}
+
+/// The extra difficulty of a long chain, growing with the logarithm of its length
+fn length_difficulty(length: usize) -> f64 {
+    let (mut added, mut ceiling, mut odd) = (0.0, 4, false);
+    let length = length.saturating_sub(2);
+    while length > ceiling {
+        added += 0.1;
+        ceiling = if odd { ceiling * 4 / 3 } else { ceiling * 3 / 2 };
+        odd = !odd;
+    }
+    added
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sudoku::Sudoku;
+
+    const GRID: &str = "417369825632158947958724316825437169791586432346912758289643571573291684164875293";
+
+    fn rating(puzzle: &str) -> Option<String> {
+        rate(&RegionMaskedSudoku::from_sudoku(&Sudoku::from_str(puzzle).unwrap())).map(|rating| rating.to_string())
+    }
+
+    #[test]
+    fn chain_length_difficulty() {
+        let added = (2 ..= 26).map(|length| format!("{:.1}", length_difficulty(length))).collect::<Vec<_>>();
+        let expected = [
+            "0.0", "0.0", "0.0", "0.0", "0.0",
+            "0.1", "0.1",
+            "0.2", "0.2",
+            "0.3", "0.3", "0.3", "0.3",
+            "0.4", "0.4", "0.4", "0.4",
+            "0.5", "0.5", "0.5", "0.5", "0.5", "0.5", "0.5", "0.5",
+        ];
+        assert_eq!(added, expected);
+    }
+
+    #[test]
+    fn known_ratings() {
+        // A solved grid needs no steps, and a single empty cell is a Hidden Single in its box
+        assert_eq!(rating(GRID).as_deref(), Some("0.0/0.0/0.0"));
+        assert_eq!(rating(&format!("0{}", &GRID[1 ..])).as_deref(), Some("1.2/1.2/1.2"));
+        // The hardest step is an XY-Chain of five cells, which is ten nodes
+        assert_eq!(rating("000290000087605000100007000000000407243000100000000009038000010020400900000089503").as_deref(), Some("6.8/1.2/1.2"));
+        // Puzzles with no solution, or which need techniques beyond those of the
+        // solver, such as Easter Monster, are unrated
+        assert_eq!(rating(&format!("44{}", &GRID[2 ..])), None);
+        assert_eq!(rating("100000002090400050006000700050903000000070000000850040700000600030009080002000001"), None);
+    }
+}
@@ -0,0 +1,590 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::expansion::Expansion;
+use crate::filter::{Filter, Uniqueness};
+use crate::generate::GenerationBase;
+use crate::logic::{Technique, Tier, DEFAULT_MAX_CHAIN_LENGTH};
+use crate::pipeline::{Pipeline, PipelineFilter, PipelineStep};
+use crate::sudoku::Sudoku;
+use crate::symmetry::DihedralSubgroup;
+use crate::template::Template;
+
+/// A recipe describes a whole pipeline, one directive per line:
+///
+/// ```text
+/// # Comments and blank lines are ignored
+/// base template
+///     ..23.....
+///     .1..4....
+///     ...
+/// define X [56789]
+/// expand plus_n n=4 symmetry=diagonal_ur_to_dl excluded=r1c1,r2c1
+/// expand minus_n n=2 symmetry=central protected=r5c5
+/// expand minimal_subpuzzles symmetry=central protected=r5c5
+/// filter has_unique_solution
+/// filter at_most_n_basic_placements n=0
+/// filter solves_with tier=fish tier_required=true
+/// filter requires_chain max_length=8
+/// filter rating_between min=2.0 max=4.5
+/// filter uses_techniques allowed=singles,pointing required=hidden_triple
+/// filter any (at_most_n_basic_placements n=2) (not (has_unique_solution))
+/// ```
+///
+/// Indented lines continue the directive above them. The `base template`
+/// directive takes the template, in which whitespace is ignored, and each
+/// `define` replaces a placeholder character in the template with some text.
+/// Alternatively, `base puzzles <path>` reads puzzles one per line from a
+/// file, or from stdin if the path is `-`, and `base pattern` takes a pattern
+/// of clues, written like a template with `.` for empty cells and any other
+/// character for a clue, and fills it in every essentially different way.
+/// `base solution_grid grid=<grid> clues=<n> symmetry=<symmetry>` searches a
+/// single solution grid for its puzzles with `n` clues, and
+/// `base random seed=<n> symmetry=<symmetry>` makes an endless stream of random
+/// minimal puzzles, and `base random_grids seed=<n>` one of uniformly random
+/// solution grids.
+/// The `filter` and `expand` directives are pipeline steps, applied in order.
+/// Filters can be combined with `not`, `any` and `all`, each followed by
+/// its inner filters in parentheses.
+pub fn parse_recipe(text: &str) -> Result<Pipeline, RecipeError> {
+    let mut base = None;
+    let mut defines = Vec::new();
+    let mut steps = Vec::new();
+
+    let directives = directives(text);
+    for directive in &directives {
+        let at_line = |error: SpecError| directive.error(error);
+        let (keyword, rest) = split_word(&directive.text);
+        match keyword {
+            "base" => {
+                if base.is_some() { return Err(at_line(SpecError::new("the base has already been given"))); }
+                let (kind, rest) = split_word(rest);
+                match kind {
+                    "template" => base = Some((directive, "template", rest.split_whitespace().collect::<String>())),
+                    "puzzles" => base = Some((directive, "puzzles", rest.trim().to_string())),
+                    "pattern" => base = Some((directive, "pattern", rest.split_whitespace().collect::<String>())),
+                    "solution_grid" => base = Some((directive, "solution_grid", rest.to_string())),
+                    "random" => base = Some((directive, "random", rest.to_string())),
+                    "random_grids" => base = Some((directive, "random_grids", rest.to_string())),
+                    _ => return Err(at_line(SpecError::new(format!("unknown base `{}`", kind)))),
+                }
+            }
+            "define" => {
+                let (placeholder, replacement) = split_word(rest);
+                if placeholder.chars().count() != 1 {
+                    return Err(at_line(SpecError::new(format!("expected a single placeholder character, found `{}`", placeholder))));
+                }
+                defines.push((placeholder.to_string(), replacement.split_whitespace().collect::<String>()));
+            }
+            "filter" => steps.push(PipelineStep::Filter(Box::new(parse_filter(rest).map_err(at_line)?))),
+            "expand" => steps.push(PipelineStep::Expansion(Box::new(parse_expansion(rest).map_err(at_line)?))),
+            _ => return Err(at_line(SpecError::new(format!("unknown directive `{}`", keyword)))),
+        }
+    }
+
+    let (directive, kind, text) = base.ok_or(RecipeError { line: 0, error: SpecError::new("no base has been given") })?;
+    let at_line = |error: SpecError| directive.error(error);
+    let base = match kind {
+        "template" => {
+            let template = defines.iter().fold(text, |template, (placeholder, replacement)| template.replace(placeholder, replacement));
+            GenerationBase::Template(Template::from_str(&template).map_err(|message| at_line(SpecError::field("template", message)))?)
+        }
+        _ if !defines.is_empty() => return Err(at_line(SpecError::new("defines only apply to a template"))),
+        "pattern" => GenerationBase::pattern(&text).map_err(|message| at_line(SpecError::field("pattern", message)))?,
+        "solution_grid" => parse_solution_grid(&text).map_err(at_line)?,
+        "random" => parse_random(&text).map_err(at_line)?,
+        "random_grids" => parse_random_grids(&text).map_err(at_line)?,
+        _ => {
+            let path = (text != "-").then(|| PathBuf::from(text));
+            GenerationBase::puzzle_list(path).map_err(|message| at_line(SpecError::field("puzzles", message)))?
+        }
+    };
+    Ok(Pipeline { base, steps })
+}
+
+/// Parse the parameters of a `solution_grid` base, e.g.
+/// `grid=<81 digits> clues=20 symmetry=central`
+fn parse_solution_grid(params: &str) -> Result<GenerationBase, SpecError> {
+    let mut fields = Fields::from_params(params)?;
+    let grid = fields.parse("grid", |value| Sudoku::from_str(value).ok_or_else(|| format!("expected a grid of 81 digits, found `{}`", value)))?;
+    let clue_count = fields.number("clues")?;
+    let symmetry = fields.parse_or("symmetry", DihedralSubgroup::Trivial, parse_symmetry)?;
+    fields.finish()?;
+    GenerationBase::solution_grid(grid, clue_count, symmetry).map_err(|message| SpecError::field("grid", message))
+}
+
+/// Parse the parameters of a `random` base, e.g. `seed=42 symmetry=central`
+fn parse_random(params: &str) -> Result<GenerationBase, SpecError> {
+    let mut fields = Fields::from_params(params)?;
+    let seed = fields.parse("seed", parse_seed)?;
+    let symmetry = fields.parse_or("symmetry", DihedralSubgroup::Trivial, parse_symmetry)?;
+    fields.finish()?;
+    Ok(GenerationBase::Random { seed, symmetry })
+}
+
+/// Parse the parameters of a `random_grids` base, e.g. `seed=42`
+fn parse_random_grids(params: &str) -> Result<GenerationBase, SpecError> {
+    let mut fields = Fields::from_params(params)?;
+    let seed = fields.parse("seed", parse_seed)?;
+    fields.finish()?;
+    Ok(GenerationBase::RandomGrids { seed })
+}
+
+fn parse_seed(value: &str) -> Result<u64, String> {
+    value.parse().map_err(|_| format!("expected a non-negative integer, found `{}`", value))
+}
+
+/// Read and parse a recipe from a file
+pub fn load_recipe(path: &Path) -> Result<Pipeline, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    parse_recipe(&text).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+/// Parse a single pipeline step, written as its kind (`filter` or `expand`),
+/// its name and then its parameters as `key=value` pairs, for example
+/// `expand plus_n n=4 symmetry=central excluded=r1c1,r9c9`.
+pub fn parse_step(spec: &str) -> Result<PipelineStep, SpecError> {
+    let (kind, rest) = split_word(spec);
+    match kind {
+        "filter" => parse_filter(rest).map(|filter| PipelineStep::Filter(Box::new(filter))),
+        "expand" => parse_expansion(rest).map(|expansion| PipelineStep::Expansion(Box::new(expansion))),
+        _ => Err(SpecError::new(format!("unknown step kind `{}`, expected `filter` or `expand`", kind))),
+    }
+}
+
+/// Parse a filter, written as its name followed by its parameters
+pub fn parse_filter(spec: &str) -> Result<Filter, SpecError> {
+    let (name, rest) = split_word(spec);
+    match name {
+        "not" => {
+            let [filter] = <[_; 1]>::try_from(parse_inner_filters(rest)?).map_err(|_| SpecError::new("expected a single inner filter"))?;
+            return Ok(Filter::Not(filter));
+        }
+        "any" => return Ok(Filter::Any(parse_inner_filters(rest)?)),
+        "all" => return Ok(Filter::All(parse_inner_filters(rest)?)),
+        _ => {}
+    }
+    let (name, mut fields) = Fields::from_spec(spec)?;
+    let filter = match name {
+        "at_most_n_basic_placements" => Filter::at_most_n_basic_placements(fields.number("n")?),
+        "solves_with_basics_after_elims" => fields.parse("elims", Filter::solves_with_basics_after_elims)?,
+        "has_any_solution" => Filter::HasAnySolution,
+        "has_unique_solution" => Filter::HasUniqueSolution,
+        "is_minimal" => Filter::IsMinimal,
+        "non_equivalent" => Filter::non_equivalent(),
+        "solves_with" => {
+            let tier = fields.parse("tier", parse_tier)?;
+            let tier_required = fields.flag("tier_required")?;
+            let uniqueness = fields.parse_or("uniqueness", Uniqueness::Forbidden, parse_uniqueness)?;
+            Filter::SolvesWith { tier, tier_required, uniqueness }
+        }
+        "requires_chain" => Filter::RequiresChain { max_length: fields.number("max_length")? },
+        "uses_techniques" => {
+            let allowed = fields.parse("allowed", parse_techniques)?;
+            let required = fields.parse_or("required", Vec::new(), parse_techniques)?;
+            Filter::uses_techniques(allowed, required)
+        }
+        "rating_between" => {
+            let (min, max) = (fields.rating("min")?, fields.rating("max")?);
+            if min > max { return Err(SpecError::field("max", format!("must be at least min={}", min))); }
+            Filter::RatingBetween { min, max }
+        }
+        _ => return Err(SpecError::new(format!("unknown filter `{}`", name))),
+    };
+    fields.finish()?;
+    Ok(filter)
+}
+
+/// Parse the inner filters of a combinator, each written within parentheses
+fn parse_inner_filters(text: &str) -> Result<Vec<Box<dyn PipelineFilter>>, SpecError> {
+    let mut filters = Vec::new();
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        if !rest.starts_with('(') { return Err(SpecError::new(format!("expected an inner filter in parentheses, found `{}`", rest))); }
+        let mut depth = 0;
+        let end = rest.char_indices().find(|&(_, c)| {
+            match c { '(' => depth += 1, ')' => depth -= 1, _ => {} }
+            depth == 0
+        }).map(|(idx, _)| idx).ok_or_else(|| SpecError::new(format!("unclosed parenthesis in `{}`", rest)))?;
+        let inner = &rest[1 .. end];
+        filters.push(parse_filter(inner).map(|filter| Box::new(filter) as Box<dyn PipelineFilter>).map_err(|err| SpecError::new(format!("inner filter `{}`: {}", inner.trim(), err)))?);
+        rest = rest[end + 1 ..].trim_start();
+    }
+    if filters.is_empty() { return Err(SpecError::new("missing inner filters")); }
+    Ok(filters)
+}
+
+/// Parse an expansion, written as its name followed by its parameters
+pub fn parse_expansion(spec: &str) -> Result<Expansion, SpecError> {
+    let (name, mut fields) = Fields::from_spec(spec)?;
+    let expansion = match name {
+        "plus_n" => {
+            let n = fields.number("n")?;
+            let symmetry = fields.parse_or("symmetry", DihedralSubgroup::Trivial, parse_symmetry)?;
+            let excluded = fields.optional("excluded").unwrap_or("");
+            Expansion::plus_n(n, symmetry, excluded).map_err(|message| SpecError::field("excluded", message))?
+        }
+        "minus_n" => {
+            let n = fields.number("n")?;
+            let symmetry = fields.parse_or("symmetry", DihedralSubgroup::Trivial, parse_symmetry)?;
+            let protected = fields.optional("protected").unwrap_or("");
+            Expansion::minus_n(n, symmetry, protected).map_err(|message| SpecError::field("protected", message))?
+        }
+        "minimal_subpuzzles" => {
+            let symmetry = fields.parse_or("symmetry", DihedralSubgroup::Trivial, parse_symmetry)?;
+            let protected = fields.optional("protected").unwrap_or("");
+            Expansion::minimal_subpuzzles(symmetry, protected).map_err(|message| SpecError::field("protected", message))?
+        }
+        _ => return Err(SpecError::new(format!("unknown expansion `{}`", name))),
+    };
+    fields.finish()?;
+    Ok(expansion)
+}
+
+/// Parse the name of a tier of logical techniques
+pub fn parse_tier(name: &str) -> Result<Tier, String> {
+    match name {
+        "basics" => Ok(Tier::Basics),
+        "fish" => Ok(Tier::Fish),
+        "wings" => Ok(Tier::Wings),
+        "chains" => Ok(Tier::Chains),
+        "als" => Ok(Tier::Als),
+        _ => Err(format!("unknown tier `{}`", name)),
+    }
+}
+
+/// Parse a comma-separated list of logical techniques, such as `naked_pair,x_wing,aic`.
+/// Chains are searched for up to the default maximum length, and `singles` stands
+/// for both Naked and Hidden Singles.
+pub fn parse_techniques(list: &str) -> Result<Vec<Technique>, String> {
+    let mut techniques = Vec::new();
+    for name in list.split(',').filter(|name| !name.is_empty()) {
+        techniques.extend(match name {
+            "singles" => vec![Technique::NakedSingle, Technique::HiddenSingle],
+            _ => vec![parse_technique(name)?],
+        });
+    }
+    Ok(techniques)
+}
+
+fn parse_technique(name: &str) -> Result<Technique, String> {
+    let technique = match name {
+        "naked_single" => Technique::NakedSingle,
+        "hidden_single" => Technique::HiddenSingle,
+        "pointing" => Technique::Pointing,
+        "claiming" => Technique::Claiming,
+        "naked_pair" => Technique::NakedSubset(2),
+        "naked_triple" => Technique::NakedSubset(3),
+        "naked_quad" => Technique::NakedSubset(4),
+        "hidden_pair" => Technique::HiddenSubset(2),
+        "hidden_triple" => Technique::HiddenSubset(3),
+        "hidden_quad" => Technique::HiddenSubset(4),
+        "x_wing" => Technique::Fish(2),
+        "swordfish" => Technique::Fish(3),
+        "jellyfish" => Technique::Fish(4),
+        "finned_x_wing" => Technique::FinnedFish(2),
+        "finned_swordfish" => Technique::FinnedFish(3),
+        "finned_jellyfish" => Technique::FinnedFish(4),
+        "sashimi_x_wing" => Technique::SashimiFish(2),
+        "sashimi_swordfish" => Technique::SashimiFish(3),
+        "sashimi_jellyfish" => Technique::SashimiFish(4),
+        "xy_wing" => Technique::XyWing,
+        "xyz_wing" => Technique::XyzWing,
+        "w_wing" => Technique::WWing,
+        "skyscraper" => Technique::Skyscraper,
+        "two_string_kite" => Technique::TwoStringKite,
+        "empty_rectangle" => Technique::EmptyRectangle,
+        "simple_coloring" => Technique::SimpleColoring,
+        "x_chain" => Technique::XChain(DEFAULT_MAX_CHAIN_LENGTH),
+        "xy_chain" => Technique::XyChain(DEFAULT_MAX_CHAIN_LENGTH),
+        "aic" => Technique::Aic(DEFAULT_MAX_CHAIN_LENGTH),
+        "als_xz" => Technique::AlsXz,
+        "als_xy_wing" => Technique::AlsXyWing,
+        "death_blossom" => Technique::DeathBlossom,
+        "bug_plus_one" => Technique::BugPlusOne,
+        _ => {
+            let kind = |prefix: &str, kinds: std::ops::RangeInclusive<usize>| name.strip_prefix(prefix)
+                .and_then(|kind| kind.parse().ok())
+                .filter(|kind| kinds.contains(kind));
+            if let Some(kind) = kind("unique_rectangle_", 1 ..= 6) {
+                Technique::UniqueRectangle(kind)
+            } else if let Some(kind) = kind("avoidable_rectangle_", 1 ..= 2) {
+                Technique::AvoidableRectangle(kind)
+            } else {
+                return Err(format!("unknown technique `{}`", name));
+            }
+        }
+    };
+    Ok(technique)
+}
+
+/// Parse whether the uniqueness techniques are `forbidden`, `allowed` or `required`
+pub fn parse_uniqueness(name: &str) -> Result<Uniqueness, String> {
+    match name {
+        "forbidden" => Ok(Uniqueness::Forbidden),
+        "allowed" => Ok(Uniqueness::Allowed),
+        "required" => Ok(Uniqueness::Required),
+        _ => Err(format!("expected `forbidden`, `allowed` or `required`, found `{}`", name)),
+    }
+}
+
+/// Parse the name of a subgroup of the dihedral symmetry group
+pub fn parse_symmetry(name: &str) -> Result<DihedralSubgroup, String> {
+    match name {
+        "trivial" => Ok(DihedralSubgroup::Trivial),
+        "central" => Ok(DihedralSubgroup::CentralSymm),
+        "horizontal" => Ok(DihedralSubgroup::HorizontalSymm),
+        "vertical" => Ok(DihedralSubgroup::VeritcalSymm),
+        "diagonal_ul_to_dr" => Ok(DihedralSubgroup::DiagonalUlToDrSymm),
+        "diagonal_ur_to_dl" => Ok(DihedralSubgroup::DiagonalUrToDlSymm),
+        "horizontal_and_vertical" => Ok(DihedralSubgroup::HorizontalAndVerticalSymm),
+        "dual_diagonal" => Ok(DihedralSubgroup::DualDiagonalSymm),
+        "fourfold_rotation" => Ok(DihedralSubgroup::FourfoldRotationSymm),
+        "full" => Ok(DihedralSubgroup::FullSymm),
+        _ => Err(format!("unknown symmetry `{}`", name)),
+    }
+}
+
+/// An error in the description of a pipeline step, naming the field at fault if there is one
+#[derive(Debug)]
+pub struct SpecError {
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl SpecError {
+    fn new<S: Into<String>>(message: S) -> Self {
+        Self { field: None, message: message.into() }
+    }
+
+    fn field<S: Into<String>>(field: &str, message: S) -> Self {
+        Self { field: Some(field.to_string()), message: message.into() }
+    }
+}
+
+impl fmt::Display for SpecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.field {
+            Some(field) => write!(f, "field `{}`: {}", field, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// An error in a recipe, along with the line on which it occurs
+#[derive(Debug)]
+pub struct RecipeError {
+    pub line: usize,
+    pub error: SpecError,
+}
+
+impl fmt::Display for RecipeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 { write!(f, "{}", self.error) }
+        else { write!(f, "line {}, {}", self.line, self.error) }
+    }
+}
+
+/// A directive of a recipe, with any indented continuation lines joined onto it
+struct Directive {
+    text: String,
+    /// Where each of the directive's lines starts within the text, along with its (one-based) line number
+    lines: Vec<(usize, usize)>,
+}
+
+impl Directive {
+
+    /// An error in this directive, reported on the line of the field at fault,
+    /// or if there isn't one, on the first line of the directive
+    fn error(&self, error: SpecError) -> RecipeError {
+        let key = error.field.as_ref().map(|field| format!("{}=", field));
+        let offset = key.and_then(|key| {
+            self.text.match_indices(&key).map(|(offset, _)| offset)
+                .find(|&offset| !self.text[.. offset].ends_with(|c: char| c.is_alphanumeric() || c == '_'))
+        });
+        let line = self.lines.iter().rev().find(|&&(start, _)| start <= offset.unwrap_or(0)).unwrap().1;
+        RecipeError { line, error }
+    }
+}
+
+/// The directives in a recipe, after removing comments and joining indented
+/// continuation lines onto the directive above.
+fn directives(text: &str) -> Vec<Directive> {
+    let mut directives: Vec<Directive> = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim_end();
+        if line.trim().is_empty() { continue; }
+        match directives.last_mut() {
+            Some(directive) if line.starts_with(char::is_whitespace) => {
+                directive.text.push(' ');
+                directive.lines.push((directive.text.len(), idx + 1));
+                directive.text.push_str(line.trim());
+            }
+            _ => directives.push(Directive { text: line.trim().to_string(), lines: vec![(0, idx + 1)] }),
+        }
+    }
+    directives
+}
+
+/// Split off the first whitespace-separated word of a string
+fn split_word(s: &str) -> (&str, &str) {
+    let s = s.trim();
+    s.split_once(char::is_whitespace).map(|(word, rest)| (word, rest.trim_start())).unwrap_or((s, ""))
+}
+
+/// The `key=value` parameters of a filter or expansion, which keeps track
+/// of which parameters have been used so that unknown ones can be reported.
+struct Fields<'a> {
+    fields: Vec<(&'a str, &'a str, bool)>,
+}
+
+impl<'a> Fields<'a> {
+
+    fn from_spec(spec: &'a str) -> Result<(&'a str, Self), SpecError> {
+        let (name, rest) = split_word(spec);
+        if name.is_empty() { return Err(SpecError::new("missing name")); }
+        Ok((name, Self::from_params(rest)?))
+    }
+
+    fn from_params(params: &'a str) -> Result<Self, SpecError> {
+        let mut fields = Vec::new();
+        for field in params.split_whitespace() {
+            let (key, value) = field.split_once('=').ok_or_else(|| SpecError::new(format!("expected `key=value`, found `{}`", field)))?;
+            if fields.iter().any(|&(k, _, _)| k == key) { return Err(SpecError::field(key, "given more than once")); }
+            fields.push((key, value, false));
+        }
+        Ok(Self { fields })
+    }
+
+    fn optional(&mut self, key: &str) -> Option<&'a str> {
+        self.fields.iter_mut().find(|(k, _, _)| *k == key).map(|(_, value, used)| { *used = true; *value })
+    }
+
+    fn required(&mut self, key: &str) -> Result<&'a str, SpecError> {
+        self.optional(key).ok_or_else(|| SpecError::field(key, "missing"))
+    }
+
+    fn parse<T, F: FnOnce(&'a str) -> Result<T, String>>(&mut self, key: &str, parser: F) -> Result<T, SpecError> {
+        parser(self.required(key)?).map_err(|message| SpecError::field(key, message))
+    }
+
+    fn parse_or<T, F: FnOnce(&'a str) -> Result<T, String>>(&mut self, key: &str, default: T, parser: F) -> Result<T, SpecError> {
+        match self.optional(key) {
+            Some(value) => parser(value).map_err(|message| SpecError::field(key, message)),
+            None => Ok(default),
+        }
+    }
+
+    fn number(&mut self, key: &str) -> Result<usize, SpecError> {
+        self.parse(key, |value| value.parse().map_err(|_| format!("expected a non-negative integer, found `{}`", value)))
+    }
+
+    fn rating(&mut self, key: &str) -> Result<f64, SpecError> {
+        self.parse(key, |value| {
+            value.parse().ok().filter(|rating: &f64| rating.is_finite()).ok_or_else(|| format!("expected a rating such as `4.5`, found `{}`", value))
+        })
+    }
+
+    fn flag(&mut self, key: &str) -> Result<bool, SpecError> {
+        self.parse_or(key, false, |value| match value {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err(format!("expected `true` or `false`, found `{}`", value)),
+        })
+    }
+
+    fn finish(self) -> Result<(), SpecError> {
+        match self.fields.iter().find(|(_, _, used)| !used) {
+            Some((key, _, _)) => Err(SpecError::field(key, "not a parameter of this step")),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indicatif::{ProgressBar, ProgressDrawTarget};
+
+    use super::*;
+
+    const GRID: &str = "417369825632158947958724316825437169791586432346912758289643571573291684164875293";
+
+    fn error(recipe: &str) -> RecipeError {
+        parse_recipe(recipe).err().unwrap()
+    }
+
+    #[test]
+    fn bad_field_values() {
+        let err = error("base puzzles -\nfilter at_most_n_basic_placements n=x\n");
+        assert_eq!((err.line, err.error.field.as_deref()), (2, Some("n")));
+        assert_eq!(err.to_string(), "line 2, field `n`: expected a non-negative integer, found `x`");
+
+        let err = error("base random seed=-1\n");
+        assert_eq!((err.line, err.error.field.as_deref()), (1, Some("seed")));
+    }
+
+    #[test]
+    fn bad_rating_bounds() {
+        let err = error("base puzzles -\nfilter rating_between min=4.5 max=2.0\n");
+        assert_eq!(err.to_string(), "line 2, field `max`: must be at least min=4.5");
+
+        for (recipe, field) in [("min=NaN max=2.0", "min"), ("min=1.0 max=inf", "max"), ("min=-inf max=2.0", "min")] {
+            let err = error(&format!("base puzzles -\nfilter rating_between {}\n", recipe));
+            assert_eq!((err.line, err.error.field.as_deref()), (2, Some(field)));
+        }
+        assert!(parse_recipe("base puzzles -\nfilter rating_between min=2.0 max=2.0\n").is_ok());
+    }
+
+    #[test]
+    fn unknown_directives() {
+        let err = error("# A comment\n\nbase puzzles -\nfliter has_unique_solution\n");
+        assert_eq!(err.to_string(), "line 4, unknown directive `fliter`");
+    }
+
+    #[test]
+    fn errors_in_continuation_lines() {
+        let recipe = "base puzzles -\nexpand plus_n\n    n=2\n    symmetry=sideways\nfilter has_unique_solution\n";
+        let err = error(recipe);
+        assert_eq!((err.line, err.error.field.as_deref()), (4, Some("symmetry")));
+
+        let err = error("base puzzles -\nexpand plus_n\n    n=2 n=3\n");
+        assert_eq!((err.line, err.error.field.as_deref()), (3, Some("n")));
+
+        // Errors in fields which aren't written out, or without a field, are
+        // reported on the first line of the directive
+        let err = error("base solution_grid\n    clues=20\n");
+        assert_eq!((err.line, err.error.field.as_deref()), (1, Some("grid")));
+        let err = error("base puzzles -\nexpand\n    plus_m n=2\n");
+        assert_eq!((err.line, err.error.field.as_deref()), (2, None));
+    }
+
+    #[test]
+    fn missing_base() {
+        let err = error("filter has_unique_solution\n");
+        assert_eq!(err.line, 0);
+        assert_eq!(err.to_string(), "no base has been given");
+    }
+
+    #[test]
+    fn valid_recipes_match_the_pipeline_they_describe() {
+        // A solution grid whose first row is made of wildcards, written over several lines
+        let recipe = format!(
+            "# The first row of a grid\nbase template\n    XXX XXX XXX\n    {}\ndefine X [123456789]\nexpand minus_n n=1\nfilter has_unique_solution\nfilter not (at_most_n_basic_placements n=0)\n",
+            &GRID[9 ..],
+        );
+        let pipeline = parse_recipe(&recipe).unwrap();
+        let template = Template::from_str(&format!("{}{}", "[123456789]".repeat(9), &GRID[9 ..])).unwrap();
+        let expected = Pipeline {
+            base: GenerationBase::Template(template),
+            steps: ["expand minus_n n=1", "filter has_unique_solution", "filter not (at_most_n_basic_placements n=0)"].iter()
+                .map(|spec| parse_step(spec).unwrap())
+                .collect(),
+        };
+
+        let bar = ProgressBar::with_draw_target(Some(100_000), ProgressDrawTarget::hidden());
+        let outputs = pipeline.iter(&bar).unwrap().collect::<Vec<_>>();
+        assert!(!outputs.is_empty());
+        assert_eq!(outputs, expected.iter(&bar).unwrap().collect::<Vec<_>>());
+    }
+}
@@ -1,6 +1,7 @@
+use std::fmt;
 use std::ops::{Index, IndexMut};
 
-#[derive(Clone, PartialEq, Eq, Hash)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Sudoku(pub [u8; 81]);
 
 impl Sudoku {
@@ -9,11 +10,47 @@ impl Sudoku {
         Self([0; 81])
     }
 
+    /// Read a Sudoku from the first 81 characters of a string. Digits 1-9 are
+    /// clues and any other character is an empty cell.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Option<Self> {
+        let mut digits = [0; 81];
+        let mut chars = s.chars();
+        for digit in digits.iter_mut() {
+            *digit = chars.next()?.to_digit(10).unwrap_or(0) as u8;
+        }
+        Some(Self(digits))
+    }
+
+    /// Read a puzzle from a line of a puzzle list, which must start with 81 digits
+    /// or `.`s. Anything after them, such as a rating or a comment, is ignored.
+    pub fn from_line(line: &str) -> Option<Self> {
+        let cells = line.trim_start().get(.. 81)?;
+        if !cells.chars().all(|c| c.is_ascii_digit() || c == '.') { return None; }
+        Self::from_str(cells)
+    }
+
     pub fn digits(&self) -> impl Iterator<Item = &u8> {
         self.0.iter()
     }
 }
 
+/// Parse a cell written as `r<row>c<col>`, such as `r4c7`, into zero-based
+/// (row, column) coordinates.
+pub fn parse_cell(s: &str) -> Result<(usize, usize), String> {
+    let coordinate = |s: &str| s.parse::<usize>().ok().filter(|x| (1 ..= 9).contains(x)).map(|x| x - 1);
+    s.strip_prefix('r')
+        .and_then(|rc| rc.split_once('c'))
+        .and_then(|(r, c)| Some((coordinate(r)?, coordinate(c)?)))
+        .ok_or_else(|| format!("expected a cell such as `r4c7`, found `{}`", s))
+}
+
+impl fmt::Display for Sudoku {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|d| write!(f, "{}", d))
+    }
+}
+
 impl Index<usize> for Sudoku {
     type Output = u8;
 
@@ -35,6 +35,7 @@ impl<'a> Symmetry<'a> {
 }
 
 /// The eight symmetries forming part of the dihedral group which acts on Sudoku grids
+#[allow(clippy::redundant_static_lifetimes)]
 pub const DIHEDRAL_SYMMETRIES: &'static [Symmetry<'static>; 8] = &[
     IDENTITY_SYMM,
     HORIZONTAL_SYMM,
@@ -103,6 +104,7 @@ pub struct DigitMapper {
 
 impl DigitMapper {
 
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Self { mapping: [0; 10], next_digit: 0 }
     }
@@ -173,6 +175,7 @@ pub const ROTATE_CENTRALLY_SYMM: Symmetry<'static> = Symmetry {
     transpose: false,
 };
 
+#[allow(clippy::redundant_static_lifetimes)]
 pub const TRIVIAL_ORBITS: [&'static [usize]; 81] = [
     &[0], &[1], &[2], &[3], &[4], &[5], &[6], &[7], &[8],
     &[9], &[10], &[11], &[12], &[13], &[14], &[15], &[16], &[17],
@@ -185,6 +188,7 @@ pub const TRIVIAL_ORBITS: [&'static [usize]; 81] = [
     &[72], &[73], &[74], &[75], &[76], &[77], &[78], &[79], &[80],
 ];
 
+#[allow(clippy::redundant_static_lifetimes)]
 pub const CENTRAL_SYMM_ORBITS: [&'static [usize]; 81] = [
     &[0, 80], &[1, 79], &[2, 78], &[3, 77], &[4, 76], &[5, 75], &[6, 74], &[7, 73], &[8, 72],
     &[9, 71], &[10, 70], &[11, 69], &[12, 68], &[13, 67], &[14, 66], &[15, 65], &[16, 64], &[17, 63],
@@ -197,6 +201,7 @@ pub const CENTRAL_SYMM_ORBITS: [&'static [usize]; 81] = [
     &[8, 72], &[7, 73], &[6, 74], &[5, 75], &[4, 76], &[3, 77], &[2, 78], &[1, 79], &[0, 80],
 ];
 
+#[allow(clippy::redundant_static_lifetimes)]
 pub const HORIZONTAL_SYMM_ORBITS: [&'static [usize]; 81] = [
     &[0, 8], &[1, 7], &[2, 6], &[3, 5], &[4], &[3, 5], &[2, 6], &[1, 7], &[0, 8],
     &[9, 17], &[10, 16], &[11, 15], &[12, 14], &[13], &[12, 14], &[11, 15], &[10, 16], &[9, 17],
@@ -209,6 +214,7 @@ pub const HORIZONTAL_SYMM_ORBITS: [&'static [usize]; 81] = [
     &[72, 80], &[73, 79], &[74, 78], &[75, 77], &[76], &[75, 77], &[74, 78], &[73, 79], &[72, 80],
 ];
 
+#[allow(clippy::redundant_static_lifetimes)]
 pub const VERTICAL_SYMM_ORBITS: [&'static [usize]; 81] = [
     &[0, 72], &[1, 73], &[2, 74], &[3, 75], &[4, 76], &[5, 77], &[6, 78], &[7, 79], &[8, 80],
     &[9, 63], &[10, 64], &[11, 65], &[12, 66], &[13, 67], &[14, 68], &[15, 69], &[16, 70], &[17, 71],
@@ -221,6 +227,7 @@ pub const VERTICAL_SYMM_ORBITS: [&'static [usize]; 81] = [
     &[0, 72], &[1, 73], &[2, 74], &[3, 75], &[4, 76], &[5, 77], &[6, 78], &[7, 79], &[8, 80],
 ];
 
+#[allow(clippy::redundant_static_lifetimes)]
 pub const DIAGONAL_UL_TO_DR_SYMM_ORBITS: [&'static [usize]; 81] = [
     &[0], &[1, 9], &[2, 18], &[3, 27], &[4, 36], &[5, 45], &[6, 54], &[7, 63], &[8, 72],
     &[1, 9], &[10], &[11, 19], &[12, 28], &[13, 37], &[14, 46], &[15, 55], &[16, 64], &[17, 73],
@@ -233,6 +240,7 @@ pub const DIAGONAL_UL_TO_DR_SYMM_ORBITS: [&'static [usize]; 81] = [
     &[8, 72], &[17, 73], &[26, 74], &[35, 75], &[44, 76], &[53, 77], &[62, 78], &[71, 79], &[80],
 ];
 
+#[allow(clippy::redundant_static_lifetimes)]
 pub const DIAGONAL_UR_TO_DL_SYMM_ORBITS: [&'static [usize]; 81] = [
     &[0, 80], &[1, 71], &[2, 62], &[3, 53], &[4, 44], &[5, 35], &[6, 26], &[7, 17], &[8],
     &[9, 79], &[10, 70], &[11, 61], &[12, 52], &[13, 43], &[14, 34], &[15, 25], &[16], &[7, 17],
@@ -245,6 +253,7 @@ pub const DIAGONAL_UR_TO_DL_SYMM_ORBITS: [&'static [usize]; 81] = [
     &[72], &[63, 73], &[54, 74], &[45, 75], &[36, 76], &[27, 77], &[18, 78], &[9, 79], &[0, 80],
 ];
 
+#[allow(clippy::redundant_static_lifetimes)]
 pub const HORIZONTAL_AND_VERTICAL_SYMM_ORBITS: [&'static [usize]; 81] = [
     &[0, 8, 72, 80], &[1, 7, 73, 79], &[2, 6, 74, 78], &[3, 5, 75, 77], &[4, 76], &[3, 5, 75, 77], &[2, 6, 74, 78], &[1, 7, 73, 79], &[0, 8, 72, 80],
     &[9, 17, 63, 71], &[10, 16, 64, 70], &[11, 15, 65, 69], &[12, 14, 66, 68], &[13, 67], &[12, 14, 66, 68], &[11, 15, 65, 69], &[10, 16, 64, 70], &[9, 17, 63, 71],
@@ -257,6 +266,7 @@ pub const HORIZONTAL_AND_VERTICAL_SYMM_ORBITS: [&'static [usize]; 81] = [
     &[0, 8, 72, 80], &[1, 7, 73, 79], &[2, 6, 74, 78], &[3, 5, 75, 77], &[4, 76], &[3, 5, 75, 77], &[2, 6, 74, 78], &[1, 7, 73, 79], &[0, 8, 72, 80],
 ];
 
+#[allow(clippy::redundant_static_lifetimes)]
 pub const DUAL_DIAGONAL_SYMM_ORBITS: [&'static [usize]; 81] = [
     &[0, 80], &[1, 9, 71, 79], &[2, 18, 62, 78], &[3, 27, 53, 77], &[4, 36, 44, 76], &[5, 35, 45, 75], &[6, 26, 54, 74], &[7, 17, 63, 73], &[8, 72],
     &[1, 9, 71, 79], &[10, 70], &[11, 19, 61, 69], &[12, 28, 52, 68], &[13, 37, 43, 67], &[14, 34, 46, 66], &[15, 25, 55, 65], &[16, 64], &[7, 17, 63, 73],
@@ -269,6 +279,7 @@ pub const DUAL_DIAGONAL_SYMM_ORBITS: [&'static [usize]; 81] = [
     &[8, 72], &[7, 17, 63, 73], &[6, 26, 54, 74], &[5, 35, 45, 75], &[4, 36, 44, 76], &[3, 27, 53, 77], &[2, 18, 62, 78], &[1, 9, 71, 79], &[0, 80],
 ];
 
+#[allow(clippy::redundant_static_lifetimes)]
 pub const FOURFOLD_ROTATION_SYMM_ORBITS: [&'static [usize]; 81] = [
     &[0, 8, 72, 80], &[1, 17, 63, 79], &[2, 26, 54, 78], &[3, 35, 45, 77], &[4, 36, 44, 76], &[5, 27, 53, 75], &[6, 18, 62, 74], &[7, 9, 71, 73], &[0, 8, 72, 80],
     &[7, 9, 71, 73], &[10, 16, 64, 70], &[11, 25, 55, 69], &[12, 34, 46, 68], &[13, 37, 43, 67], &[14, 28, 52, 66], &[15, 19, 61, 65], &[10, 16, 64, 70], &[1, 17, 63, 79],
@@ -281,6 +292,7 @@ pub const FOURFOLD_ROTATION_SYMM_ORBITS: [&'static [usize]; 81] = [
     &[0, 8, 72, 80], &[7, 9, 71, 73], &[6, 18, 62, 74], &[5, 27, 53, 75], &[4, 36, 44, 76], &[3, 35, 45, 77], &[2, 26, 54, 78], &[1, 17, 63, 79], &[0, 8, 72, 80],
 ];
 
+#[allow(clippy::redundant_static_lifetimes)]
 pub const FULL_SYMM_ORBITS: [&'static [usize]; 81] = [
     &[0, 8, 72, 80], &[1, 7, 9, 17, 63, 71, 73, 79], &[2, 6, 18, 26, 54, 62, 74, 78], &[3, 5, 27, 35, 45, 53, 75, 77], &[4, 36, 44, 76], &[3, 5, 27, 35, 45, 53, 75, 77], &[2, 6, 18, 26, 54, 62, 74, 78], &[1, 7, 9, 17, 63, 71, 73, 79], &[0, 8, 72, 80],
     &[1, 7, 9, 17, 63, 71, 73, 79], &[10, 16, 64, 70], &[11, 15, 19, 25, 55, 61, 65, 69], &[12, 14, 28, 34, 46, 52, 66, 68], &[13, 37, 43, 67], &[12, 14, 28, 34, 46, 52, 66, 68], &[11, 15, 19, 25, 55, 61, 65, 69], &[10, 16, 64, 70], &[1, 7, 9, 17, 63, 71, 73, 79],
@@ -8,17 +8,19 @@ pub enum TemplateDigit {
 pub struct Template([TemplateDigit; 81]);
 
 impl Template {
-    pub fn from_str(s: &str) -> Self {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Self, String> {
         let mut digits = Vec::with_capacity(81);
         let mut chars = s.chars();
         while let Some(digit) = Self::next_digit(&mut chars) { digits.push(digit); }
-        Self(digits.try_into().unwrap())
+        digits.try_into().map(Self).map_err(|digits: Vec<_>| format!("expected 81 cells, found {}", digits.len()))
     }
 
     pub fn digits(&self) -> impl Iterator<Item = &TemplateDigit> {
         self.0.iter()
     }
 
+    #[allow(clippy::manual_range_contains)]
     fn next_digit<I: Iterator<Item = char>>(chars: &mut I) -> Option<TemplateDigit> {
         chars.next().map(|c| match c {
             d @ '1' ..= '9' => TemplateDigit::Given(d.to_digit(10).unwrap() as u8),